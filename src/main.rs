@@ -1,17 +1,23 @@
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+use clap::Parser;
 use glam::Vec3;
-use rand::random;
 
 use crate::my_mod::angle::Angle;
 use crate::my_mod::camera::Camera;
+use crate::my_mod::cli::Args;
 use crate::my_mod::intensity::Intensity;
 use crate::my_mod::material::{Attenuation, Material};
-use crate::my_mod::ppm::SavableToPPM;
 use crate::my_mod::renderer::Renderer;
 use crate::my_mod::resolution::Resolution;
 use crate::my_mod::scene::{Scene, SceneObject};
+use crate::my_mod::scene_file;
 use crate::my_mod::sphere::Sphere;
+use crate::my_mod::tonemap::DisplayTransform;
+use crate::my_mod::utils::{random, reseed};
+use crate::my_mod::writer::ImageFormat;
 
 mod my_mod;
 
@@ -85,14 +91,146 @@ fn get_scene() -> Scene {
         )
     ));
 
-    Scene(objects)
+    Scene::new(objects)
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    let format = match args.format {
+        Some(format) => format,
+        None => ImageFormat::from_extension(&args.output).ok_or_else(|| format!(
+            "can't guess the output format of {}, use --format",
+            args.output.display(),
+        ))?,
+    };
+    if args.alpha && !format.supports_alpha() {
+        return Err(format!("{:?} output has no alpha channel, use png, png16 or exr", format).into());
+    }
+
+    if args.white_point.is_nan() || args.white_point <= 0. {
+        return Err("--white-point must be positive".into());
+    }
+    let display = DisplayTransform::new(args.tonemap)
+        .exposure(args.exposure)
+        .white_point(args.white_point);
+
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()?;
+    }
+
+    if let Some(seed) = args.seed {
+        reseed(seed);
+    }
+
+    let started = Instant::now();
+    let (mut world, mut renderer) = match &args.scene {
+        Some(scene_file) => scene_file::load(scene_file)?,
+        None => default_scene(),
+    };
+    if args.verbose {
+        let Scene { objects, lights } = &world;
+        eprintln!("loaded {} objects and {} lights in {:.2?}", objects.len(), lights.len(), started.elapsed());
+    }
+
+    if args.width.is_some() || args.height.is_some() {
+        let Resolution { width, height } = renderer.image_resolution();
+        let aspect_ratio = width as f32 / height as f32;
+        let resolution = match (args.width, args.height) {
+            (Some(width), Some(height)) => Resolution { width, height },
+            (Some(width), None) => Resolution { width, height: (width as f32 / aspect_ratio) as usize },
+            (None, Some(height)) => Resolution { width: (height as f32 * aspect_ratio) as usize, height },
+            (None, None) => unreachable!(),
+        };
+        if resolution.width < 2 || resolution.height < 2 {
+            return Err(format!("resolution {}x{} is too small", resolution.width, resolution.height).into());
+        }
+        renderer = renderer.resolution(resolution);
+    }
+    if let Some(spp) = args.spp {
+        if spp == 0 {
+            return Err("--spp must be positive".into());
+        }
+        renderer = renderer.samples_per_pixel(spp);
+    }
+    if let Some(max_depth) = args.max_depth {
+        renderer = renderer.max_depth(max_depth);
+    }
+    if let Some(strategy) = args.bvh {
+        renderer = renderer.split_strategy(strategy);
+    }
+    if let Some(integrator) = args.integrator {
+        renderer = renderer.integrator(integrator);
+    }
+    if args.no_light_sampling {
+        renderer = renderer.light_sampling(false);
+    }
+    if let Some(crop) = args.crop {
+        let Resolution { width, height } = renderer.image_resolution();
+        if crop.x1 > width || crop.y1 > height {
+            return Err(format!("crop window exceeds the {}x{} image", width, height).into());
+        }
+        renderer = renderer.crop(crop);
+    }
+    if let Some(seed) = args.seed {
+        renderer = renderer.seed(seed);
+    }
+    if args.frames == 0 {
+        return Err("--frames must be positive".into());
+    }
+    if args.frames > 1 && !world.is_animated() {
+        return Err("--frames needs instances with keyframes in the scene".into());
+    }
+    renderer = renderer
+        .alpha(args.alpha)
+        .report_progress(!args.quiet);
+
+    if args.verbose {
+        let Resolution { width, height } = renderer.image_resolution();
+        eprintln!("rendering {}x{} with {} threads", width, height, rayon::current_num_threads());
+    }
+
+    // the top-level BVH is built once and only refit as instances move, their meshes keep their BVHs
+    let mut tlas = renderer.tlas(&world);
+    for frame in 0..args.frames {
+        if frame > 0 {
+            let started = Instant::now();
+            world.animate(frame as f32);
+            tlas.refit(&world);
+            if args.verbose {
+                eprintln!("moved to frame {} in {:.2?}", frame, started.elapsed());
+            }
+        }
+
+        let started = Instant::now();
+        let image = renderer.render(&world, &tlas);
+        if args.verbose {
+            eprintln!("rendered in {:.2?}", started.elapsed());
+        }
+
+        let output = if args.frames > 1 { frame_path(&args.output, frame) } else { args.output.clone() };
+        format.save(&image, &display, args.alpha, &output)
+            .map_err(|e| format!("{}: {}", output.display(), e))?;
+        if !args.quiet {
+            eprintln!("saved {}", output.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn default_scene() -> (Scene, Renderer) {
     let samples_per_pixel = 500;
     let max_depth = 50;
 
-    let aspect_ratio = 3. / 2. as f32;
+    let aspect_ratio = 3. / 2.;
     let resolution = {
         let width = 720;
         let height = (width as f32 / aspect_ratio) as usize;
@@ -119,13 +257,19 @@ fn main() -> Result<(), Box<dyn Error>> {
         )
     };
 
-    let world = get_scene();
-
-    Renderer::new(camera, resolution)
+    let renderer = Renderer::new(camera, resolution)
         .samples_per_pixel(samples_per_pixel)
-        .max_depth(max_depth)
-        .render(&world)
-        .save_to_ppm("image.ppm")?;
+        .max_depth(max_depth);
 
-    Ok(())
+    (get_scene(), renderer)
+}
+
+/// `image.png` becomes `image-0007.png` for frame 7.
+fn frame_path(output: &Path, frame: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(extension) => format!("{}-{:04}.{}", stem, frame, extension.to_string_lossy()),
+        None => format!("{}-{:04}", stem, frame),
+    };
+    output.with_file_name(name)
 }
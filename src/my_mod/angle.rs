@@ -2,7 +2,6 @@ use std::f32::consts::PI;
 
 pub enum Angle {
     Degrees(f32),
-    #[allow(dead_code)]
     Radians(f32),
 }
 
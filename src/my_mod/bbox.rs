@@ -1,5 +1,8 @@
 use glam::Vec3;
 
+use crate::my_mod::hittable::Accuracy;
+use crate::my_mod::ray::Ray;
+
 #[derive(Copy, Clone, Debug)]
 pub struct BBox {
     pub min: Vec3,
@@ -7,6 +10,23 @@ pub struct BBox {
 }
 
 impl BBox {
+    /// The identity for `merge`.
+    pub fn empty() -> BBox {
+        BBox {
+            min: Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY)
+        }
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        (self.max - self.min).max(Vec3::ZERO)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.extent();
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
     pub fn center(&self) -> Vec3 {
         (self.min + self.max) / 2.
     }
@@ -17,6 +37,20 @@ impl BBox {
             && self.min.z <= z.z && z.z <= self.max.z
     }
 
+    /// The part of `range` the ray spends inside the box.
+    pub fn intersect(&self, ray: &Ray, range: &Accuracy) -> Option<(f32, f32)> {
+        let inv_dir = Vec3::ONE / ray.direction();
+        let t0 = (self.min - ray.origin()) * inv_dir;
+        let t1 = (self.max - ray.origin()) * inv_dir;
+        let enter = t0.min(t1).max_element().max(range.min);
+        let exit = t0.max(t1).min_element().min(range.max);
+        if enter < exit {
+            Some((enter, exit))
+        } else {
+            None
+        }
+    }
+
     pub fn merge(a: &BBox, b: &BBox) -> BBox {
         BBox {
             min: Vec3::new(
@@ -0,0 +1,122 @@
+use glam::Vec3;
+
+use crate::my_mod::bbox::BBox;
+use crate::my_mod::bvh::{Boundable, SplitStrategy, BVH};
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::mesh::TriangleMesh;
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::{LightSample, SceneObject};
+use crate::my_mod::time::{Shutter, TimePoint};
+use crate::my_mod::utils::random;
+
+/// Bottom-level acceleration structure: a group of objects with its own BVH, built once.
+///
+/// A BLAS is a single object to the scene, so sharing it between instances keeps one copy of the
+/// geometry and its hierarchy, and the top-level BVH only has to bound the instances.
+#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub struct BLAS {
+    objects: Vec<Box<dyn SceneObject>>,
+    bvh: BVH,
+    bbox: BBox,
+    lights: Vec<usize>,
+}
+
+impl BLAS {
+    /// Moving objects are bounded over `shutter`, which has to cover the shutter of the camera.
+    pub fn new(objects: Vec<Box<dyn SceneObject>>, strategy: SplitStrategy, shutter: Shutter) -> BLAS {
+        assert!(objects.iter().all(|object| object.is_bounded()), "a BLAS can only hold bounded objects");
+        let bboxes = objects.iter().map(|object| object.bbox(&shutter)).collect::<Vec<_>>();
+        let bbox = bboxes.iter().fold(BBox::empty(), |a, b| BBox::merge(&a, b));
+        let lights = (0..objects.len()).filter(|i| objects[*i].is_emissive()).collect();
+        BLAS {
+            bvh: BVH::new(&bboxes, strategy),
+            objects,
+            bbox,
+            lights,
+        }
+    }
+
+    pub fn from_mesh(mesh: TriangleMesh, strategy: SplitStrategy) -> BLAS {
+        let triangles = mesh.triangles()
+            .into_iter()
+            .map(|triangle| Box::new(triangle) as Box<dyn SceneObject>)
+            .collect();
+        BLAS::new(triangles, strategy, Shutter::default())
+    }
+}
+
+impl Hittable for BLAS {
+    fn hit(&self, ray: &Ray, range: &Accuracy) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, range, |index, range| self.objects[index].hit(ray, range))
+    }
+}
+
+impl Boundable for BLAS {
+    fn bbox(&self, _shutter: &Shutter) -> BBox {
+        self.bbox
+    }
+}
+
+impl SceneObject for BLAS {
+    fn is_emissive(&self) -> bool {
+        !self.lights.is_empty()
+    }
+
+    /// Samples one of the emissive members picked uniformly.
+    fn sample_towards(&self, origin: Vec3, time: TimePoint) -> Option<LightSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let light = &self.objects[self.lights[random::<usize>() % self.lights.len()]];
+        let sample = light.sample_towards(origin, time)?;
+
+        // the sampled member may be behind another one, which is what is seen along the direction
+        let ray = Ray::new(&origin, &sample.direction, time);
+        let nearest = self.hit(&ray, &Accuracy { min: 1e-4, max: f32::INFINITY })?;
+        Some(LightSample {
+            distance: nearest.t(),
+            pdf: self.pdf_towards(origin, sample.direction, time),
+            ..sample
+        })
+    }
+
+    fn hit_counted(&self, ray: &Ray, range: &Accuracy) -> (Option<HitRecord<'_>>, usize) {
+        let mut steps = 0;
+        let (hit, bvh_steps) = self.bvh.hit_counted(ray, range, |index, range| {
+            let (hit, object_steps) = self.objects[index].hit_counted(ray, range);
+            steps += object_steps;
+            hit
+        });
+        (hit, steps + bvh_steps)
+    }
+
+    fn transmittance(&self, ray: &Ray, range: &Accuracy) -> f32 {
+        let mut transmittance = 1.;
+        self.bvh.hit(ray, range, |index, range| {
+            transmittance *= self.objects[index].transmittance(ray, range);
+            if transmittance == 0. {
+                range.max = f32::NEG_INFINITY;
+            }
+            None
+        });
+        transmittance
+    }
+
+    /// Sums the densities of all emissive members along the direction, any of them could have been sampled.
+    fn pdf_towards(&self, origin: Vec3, direction: Vec3, time: TimePoint) -> f32 {
+        if self.lights.is_empty() {
+            return 0.;
+        }
+        let ray = Ray::new(&origin, &direction, time);
+        let mut pdf = 0.;
+        // rejecting every hit keeps the range open, so all members along the ray are visited
+        self.bvh.hit(&ray, &Accuracy { min: 1e-4, max: f32::INFINITY }, |index, _| {
+            if self.lights.binary_search(&index).is_ok() {
+                pdf += self.objects[index].pdf_towards(origin, direction, time);
+            }
+            None
+        });
+        pdf / self.lights.len() as f32
+    }
+}
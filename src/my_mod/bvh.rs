@@ -1,29 +1,28 @@
-use std::cmp::min_by;
 use std::cmp::Ordering::{Greater, Less};
 use std::fmt::Debug;
-use std::mem::swap;
+use std::str::FromStr;
 
 use glam::Vec3;
 
 use crate::my_mod::bbox::BBox;
-use crate::my_mod::bvh::BVH::{Leaf, Node};
+use crate::my_mod::bvh::BuildNode::{Leaf, Node};
 use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
 use crate::my_mod::ray::Ray;
-use crate::my_mod::scene::SceneObject;
+use crate::my_mod::time::Shutter;
 use crate::my_mod::utils::random_on_unit_sphere;
-use crate::my_mod::vec3;
 use crate::Scene;
 
 pub trait Boundable {
-    fn bbox(&self) -> BBox;
+    /// Bounds of everywhere the object is while the shutter is open.
+    fn bbox(&self, shutter: &Shutter) -> BBox;
 }
 
-
+/// Binary tree produced by the builder, flattened into `BVH` afterwards.
 #[derive(Debug)]
-enum BVH {
+enum BuildNode {
     Node {
-        child_a: Box<BVH>,
-        child_b: Box<BVH>,
+        child_a: Box<BuildNode>,
+        child_b: Box<BuildNode>,
         bbox: BBox
     },
     Leaf {
@@ -32,26 +31,61 @@ enum BVH {
     }
 }
 
-impl BVH {
-    fn new_impl(objects: &[Box<dyn SceneObject>], indexes: Vec<usize>) -> Self {
+/// How the builder divides a set of objects between the two children of a node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SplitStrategy {
+    /// Halves the objects along the longest axis of their centers.
+    Median,
+    /// Minimizes the surface area heuristic over binned candidate planes on the principal axes.
+    #[default]
+    Sah,
+    /// Halves the objects along a random direction, the original non-reproducible builder.
+    Random,
+}
+
+impl FromStr for SplitStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "median" => Ok(SplitStrategy::Median),
+            "sah" => Ok(SplitStrategy::Sah),
+            "random" => Ok(SplitStrategy::Random),
+            _ => Err(format!("unknown split strategy '{}', expected median, sah or random", s)),
+        }
+    }
+}
+
+/// Below this depth the SAH and random splits give way to the median split,
+/// which keeps the tree shallow enough for the fixed traversal stack.
+const MAX_UNBALANCED_DEPTH: usize = 64;
+
+const STACK_SIZE: usize = 128;
+
+impl BuildNode {
+    fn new(bboxes: &[BBox], indexes: Vec<usize>, strategy: SplitStrategy, depth: usize) -> Self {
         if indexes.is_empty() {
             panic!("There should be at least one Hittable.")
         }
 
         if indexes.len() == 1 {
             let index = indexes[0];
-            let bbox = objects[index].bbox();
+            let bbox = bboxes[index];
             Leaf {
                 index,
                 bbox,
             }
         } else {
             let bbox = indexes.iter()
-                .map(|i| objects[*i].bbox())
-                .fold(BBox { min: vec3::zero(), max: vec3::zero() }, |acc, bbox| BBox::merge(&acc, &bbox));
-            let (child_a, child_b) = split(objects, indexes);
-            let child_a = Box::new(BVH::new_impl(objects, child_a));
-            let child_b = Box::new(BVH::new_impl(objects, child_b));
+                .fold(BBox::empty(), |acc, i| BBox::merge(&acc, &bboxes[*i]));
+            let strategy = if depth < MAX_UNBALANCED_DEPTH { strategy } else { SplitStrategy::Median };
+            let (child_a, child_b) = match strategy {
+                SplitStrategy::Median => split_median(bboxes, indexes),
+                SplitStrategy::Sah => split_sah(bboxes, indexes),
+                SplitStrategy::Random => split_random(bboxes, indexes),
+            };
+            let child_a = Box::new(BuildNode::new(bboxes, child_a, strategy, depth + 1));
+            let child_b = Box::new(BuildNode::new(bboxes, child_b, strategy, depth + 1));
             Node {
                 child_a,
                 child_b,
@@ -60,14 +94,106 @@ impl BVH {
         }
     }
 
-    fn new(scene: &Scene) -> Self {
-        let Scene(objects) = scene;
-        let indexes = (0..objects.len()).collect::<Vec<usize>>();
-        Self::new_impl(objects, indexes)
+    fn bbox(&self) -> &BBox {
+        match self {
+            Node { bbox, .. } => bbox,
+            Leaf { bbox, .. } => bbox
+        }
+    }
+}
+
+fn centers_bbox(bboxes: &[BBox], indexes: &[usize]) -> BBox {
+    indexes.iter()
+        .map(|i| bboxes[*i].center())
+        .fold(BBox::empty(), |acc, c| BBox::merge(&acc, &BBox { min: c, max: c }))
+}
+
+fn split_median(bboxes: &[BBox], mut indexes: Vec<usize>) -> (Vec<usize>, Vec<usize>) {
+    let len = indexes.len();
+    if len < 2 {
+        panic!("There should be at least 2 objects.");
     }
+
+    let extent = centers_bbox(bboxes, &indexes).extent();
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+
+    indexes.select_nth_unstable_by(len / 2, |a, b| {
+        f32::total_cmp(&bboxes[*a].center()[axis], &bboxes[*b].center()[axis])
+    });
+    let child_b = indexes.split_off(len / 2);
+    (indexes, child_b)
 }
 
-fn split(objects: &[Box<dyn SceneObject>], mut indexes: Vec<usize>) -> (Vec<usize>, Vec<usize>) {
+const SAH_BINS: usize = 16;
+
+fn split_sah(bboxes: &[BBox], indexes: Vec<usize>) -> (Vec<usize>, Vec<usize>) {
+    let len = indexes.len();
+    if len < 2 {
+        panic!("There should be at least 2 objects.");
+    }
+    if len <= 4 {
+        return split_median(bboxes, indexes);
+    }
+
+    let centers = centers_bbox(bboxes, &indexes);
+    let bin_of = |i: usize, axis: usize| -> usize {
+        let extent = centers.max[axis] - centers.min[axis];
+        let offset = (bboxes[i].center()[axis] - centers.min[axis]) / extent;
+        ((offset * SAH_BINS as f32) as usize).min(SAH_BINS - 1)
+    };
+
+    // (cost, axis, first bin of the second child)
+    let mut best: Option<(f32, usize, usize)> = None;
+
+    for axis in 0..3 {
+        if centers.max[axis] - centers.min[axis] <= 0. {
+            continue;
+        }
+
+        let mut bins = [(BBox::empty(), 0usize); SAH_BINS];
+        for i in &indexes {
+            let (bbox, count) = &mut bins[bin_of(*i, axis)];
+            *bbox = BBox::merge(bbox, &bboxes[*i]);
+            *count += 1;
+        }
+
+        // right_costs[k] is the cost of bins k.. as one child
+        let mut right_costs = [0.; SAH_BINS];
+        let (mut bbox, mut count) = (BBox::empty(), 0);
+        for k in (1..SAH_BINS).rev() {
+            bbox = BBox::merge(&bbox, &bins[k].0);
+            count += bins[k].1;
+            right_costs[k] = bbox.surface_area() * count as f32;
+        }
+
+        let (mut bbox, mut count) = (BBox::empty(), 0);
+        for k in 1..SAH_BINS {
+            bbox = BBox::merge(&bbox, &bins[k - 1].0);
+            count += bins[k - 1].1;
+            if count == 0 || count == len {
+                continue;
+            }
+            let cost = bbox.surface_area() * count as f32 + right_costs[k];
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, k));
+            }
+        }
+    }
+
+    match best {
+        // every center is in the same place, nothing to optimize
+        None => split_median(bboxes, indexes),
+        Some((_, axis, k)) => indexes.into_iter().partition(|i| bin_of(*i, axis) < k),
+    }
+}
+
+fn split_random(bboxes: &[BBox], mut indexes: Vec<usize>) -> (Vec<usize>, Vec<usize>) {
     let len = indexes.len();
     if len < 2 {
         panic!("There should be at least 2 objects.");
@@ -75,9 +201,7 @@ fn split(objects: &[Box<dyn SceneObject>], mut indexes: Vec<usize>) -> (Vec<usiz
 
     let axis = random_on_unit_sphere();
     indexes.sort_by(|a, b| {
-        let a = objects.get(*a).unwrap();
-        let b = objects.get(*b).unwrap();
-        if Vec3::dot(a.bbox().center(), axis) < Vec3::dot(b.bbox().center(), axis) {
+        if Vec3::dot(bboxes[*a].center(), axis) < Vec3::dot(bboxes[*b].center(), axis) {
             Less
         } else {
             Greater
@@ -87,95 +211,293 @@ fn split(objects: &[Box<dyn SceneObject>], mut indexes: Vec<usize>) -> (Vec<usiz
     (indexes[0.. len / 2].to_vec(), indexes[len/2..len].to_vec())
 }
 
+const LEAF: u8 = u8::MAX;
+
+#[derive(Debug, Copy, Clone)]
+struct LinearNode {
+    bbox: BBox,
+    /// The object index of a leaf or the index of the second child of an interior node,
+    /// the first child always follows its parent.
+    offset: u32,
+    /// The axis along which the children of an interior node are separated the most, `LEAF` for leaves.
+    axis: u8,
+}
+
+/// Bounding volume hierarchy stored as a depth-first array of nodes.
+#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub struct BVH {
+    nodes: Vec<LinearNode>,
+}
+
 impl BVH {
-    fn bbox(&self) -> &BBox {
-        match self {
-            BVH::Node { bbox, .. } => bbox,
-            BVH::Leaf { bbox, .. } => bbox
+    /// Builds the hierarchy over objects with the given bounding boxes,
+    /// leaves refer to objects by their index in `bboxes`.
+    pub fn new(bboxes: &[BBox], strategy: SplitStrategy) -> Self {
+        let mut nodes = Vec::with_capacity(2 * bboxes.len());
+        if !bboxes.is_empty() {
+            let indexes = (0..bboxes.len()).collect::<Vec<usize>>();
+            flatten(&BuildNode::new(bboxes, indexes, strategy, 0), &mut nodes);
         }
+        Self { nodes }
     }
-}
 
-pub struct BVHScene<'a> {
-    bvh: BVH,
-    scene: &'a Scene
-}
+    /// Bounds of all the objects, `None` without any.
+    pub fn bbox(&self) -> Option<BBox> {
+        self.nodes.first().map(|node| node.bbox)
+    }
 
-impl BVHScene<'_> {
-    pub fn new(scene: &Scene) -> BVHScene {
-        BVHScene {
-            bvh: BVH::new(scene),
-            scene
+    /// Recomputes the bounds of the nodes for the new boxes of the same objects, keeping the tree.
+    pub fn refit(&mut self, bboxes: &[BBox]) {
+        // children come after their parent, so going backwards finishes them first
+        for index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[index];
+            self.nodes[index].bbox = if node.axis == LEAF {
+                bboxes[node.offset as usize]
+            } else {
+                BBox::merge(&self.nodes[index + 1].bbox, &self.nodes[node.offset as usize].bbox)
+            };
         }
     }
-}
 
-fn find_hit<'a>(node: &BVH, scene: &'a Scene, ray: &Ray, range: &Accuracy) -> Option<HitRecord<'a>> {
-    if !has_intersection(ray, node.bbox()) {
-        return None
+    /// Finds the closest hit, `hit_object` is called for every leaf with the current search range.
+    /// It may shrink the range, emptying it ends the search.
+    pub fn hit<'a, F>(&self, ray: &Ray, range: &Accuracy, hit_object: F) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, &mut Accuracy) -> Option<HitRecord<'a>>,
+    {
+        self.traverse::<F, false>(ray, range, hit_object).0
     }
 
-    match node {
-        BVH::Node { child_a, child_b, .. } => {
-            let hit_a =  find_hit(child_a, scene, ray, range);
-            let hit_b =  find_hit(child_b, scene, ray, range);
-            min_by(hit_a, hit_b, |a, b| {
-                fn convert(x: &Option<HitRecord>) -> f32 {
-                    match x {
-                        None => f32::INFINITY,
-                        Some(hit) => hit.t()
-                    }
+    /// Like `hit`, but also counts the nodes visited, those of the objects in the leaves aside.
+    pub fn hit_counted<'a, F>(&self, ray: &Ray, range: &Accuracy, hit_object: F) -> (Option<HitRecord<'a>>, usize)
+    where
+        F: FnMut(usize, &mut Accuracy) -> Option<HitRecord<'a>>,
+    {
+        self.traverse::<F, true>(ray, range, hit_object)
+    }
+
+    /// Counting is compiled out of the traversal without `COUNT`.
+    fn traverse<'a, F, const COUNT: bool>(
+        &self,
+        ray: &Ray,
+        range: &Accuracy,
+        mut hit_object: F,
+    ) -> (Option<HitRecord<'a>>, usize)
+    where
+        F: FnMut(usize, &mut Accuracy) -> Option<HitRecord<'a>>,
+    {
+        if self.nodes.is_empty() {
+            return (None, 0);
+        }
+
+        let origin = ray.origin();
+        let inv_dir = Vec3::ONE / ray.direction();
+        let negative_dir = [inv_dir.x < 0., inv_dir.y < 0., inv_dir.z < 0.];
+
+        let mut range = Accuracy { min: range.min, max: range.max };
+        let mut closest = None;
+
+        let mut stack = [0u32; STACK_SIZE];
+        let mut stack_size = 1;
+        let mut steps = 0;
+
+        while stack_size > 0 && range.min <= range.max {
+            stack_size -= 1;
+            if COUNT {
+                steps += 1;
+            }
+            let index = stack[stack_size] as usize;
+            let node = &self.nodes[index];
+
+            if !has_intersection(&node.bbox, origin, inv_dir, &range) {
+                continue;
+            }
+
+            if node.axis == LEAF {
+                if let Some(hit) = hit_object(node.offset as usize, &mut range) {
+                    range.max = hit.t();
+                    closest = Some(hit);
                 }
-                let a = convert(a);
-                let b = convert(b);
-                if a < b {
-                    Less
+            } else {
+                // the far child goes deeper into the stack so the near one is visited first
+                let (near, far) = if negative_dir[node.axis as usize] {
+                    (node.offset, index as u32 + 1)
                 } else {
-                    Greater
-                }
-            })
-        }
-        BVH::Leaf { index, .. } => {
-            let Scene(scene) = scene;
-            scene[*index].hit(ray, range)
+                    (index as u32 + 1, node.offset)
+                };
+                stack[stack_size] = far;
+                stack[stack_size + 1] = near;
+                stack_size += 2;
+            }
         }
+
+        (closest, steps)
     }
 }
 
+fn flatten(node: &BuildNode, nodes: &mut Vec<LinearNode>) -> u32 {
+    let index = nodes.len();
+    match node {
+        Leaf { index: object, bbox } => {
+            nodes.push(LinearNode { bbox: *bbox, offset: *object as u32, axis: LEAF });
+        }
+        Node { child_a, child_b, bbox } => {
+            let separation = (child_b.bbox().center() - child_a.bbox().center()).abs();
+            let axis = if separation.x >= separation.y && separation.x >= separation.z {
+                0
+            } else if separation.y >= separation.z {
+                1
+            } else {
+                2
+            };
 
-impl Hittable for BVHScene<'_> {
-    fn hit(&self, ray: &Ray, range: &Accuracy) -> Option<HitRecord> {
-        find_hit(&self.bvh, self.scene, ray, range)
+            nodes.push(LinearNode { bbox: *bbox, offset: 0, axis });
+            flatten(child_a, nodes);
+            nodes[index].offset = flatten(child_b, nodes);
+        }
     }
+    index as u32
 }
 
-fn has_intersection(ray: &Ray, bbox: &BBox) -> bool {
-    let o = ray.origin();
+/// Slab test of the ray against the box, restricted to the current search range.
+fn has_intersection(bbox: &BBox, origin: Vec3, inv_dir: Vec3, range: &Accuracy) -> bool {
+    let t0 = (bbox.min - origin) * inv_dir;
+    let t1 = (bbox.max - origin) * inv_dir;
+    let near = t0.min(t1);
+    let far = t0.max(t1);
+
+    let enter = near.x.max(near.y).max(near.z).max(range.min);
+    let exit = far.x.min(far.y).min(far.z).min(range.max);
 
-    if bbox.contains(&o) {
-        return true
+    enter <= exit
+}
+
+/// Top-level acceleration structure: a BVH over the objects of a scene.
+///
+/// Meshes and groups are `BLAS`es with hierarchies of their own, so this only bounds them as single
+/// objects. When only instances move between frames, `refit` updates it without touching the `BLAS`es.
+#[allow(clippy::upper_case_acronyms)]
+pub struct TLAS {
+    bvh: BVH,
+    /// Scene indices of the objects in the BVH, in the order of their bounding boxes.
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+    shutter: Shutter,
+}
+
+impl TLAS {
+    pub fn new(scene: &Scene, strategy: SplitStrategy, shutter: Shutter) -> TLAS {
+        let Scene { objects, .. } = scene;
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = (0..objects.len())
+            .partition(|index| objects[*index].is_bounded());
+        let bboxes = bounded.iter().map(|index| objects[*index].bbox(&shutter)).collect::<Vec<_>>();
+        TLAS { bvh: BVH::new(&bboxes, strategy), bounded, unbounded, shutter }
     }
 
-    let d = ray.direction();
+    /// Updates the bounds after objects of `scene`, the one the tree was built for, have moved.
+    ///
+    /// The tree keeps its shape, so this is much cheaper than a new one but gets slower to
+    /// traverse the further the objects move from where they were.
+    pub fn refit(&mut self, scene: &Scene) {
+        assert_eq!(
+            self.bounded.len() + self.unbounded.len(),
+            scene.objects.len(),
+            "a TLAS can only be refit to the scene it was built for",
+        );
+        let bboxes = self.bounded.iter().map(|index| scene.objects[*index].bbox(&self.shutter)).collect::<Vec<_>>();
+        self.bvh.refit(&bboxes);
+    }
+}
 
-    let mn = bbox.min - o;
-    let mut mn: [f32; 3] = [mn.x / d.x, mn.y / d.y, mn.z / d.z];
+/// A scene ready for ray queries through its `TLAS`.
+pub struct BVHScene<'a> {
+    tlas: &'a TLAS,
+    scene: &'a Scene,
+}
 
-    let mx = bbox.max - o;
-    let mut mx: [f32; 3] = [mx.x / d.x, mx.y / d.y, mx.z / d.z];
+impl<'a> BVHScene<'a> {
+    pub fn new(tlas: &'a TLAS, scene: &'a Scene) -> BVHScene<'a> {
+        BVHScene { tlas, scene }
+    }
 
-    if d.x < 0. {
-        swap(&mut mn[0], &mut mx[0]);
+    /// Bounds of the bounded objects, `None` without any.
+    pub fn bbox(&self) -> Option<BBox> {
+        self.tlas.bvh.bbox()
     }
-    if d.y < 0. {
-        swap(&mut mn[1], &mut mx[1]);
+
+    /// Like `hit`, but also tells which object of the scene was hit.
+    pub fn hit_object(&self, ray: &Ray, range: &Accuracy) -> Option<(usize, HitRecord<'a>)> {
+        let Scene { objects, .. } = self.scene;
+        let mut range = Accuracy { min: range.min, max: range.max };
+
+        let mut closest = None;
+        for &index in &self.tlas.unbounded {
+            if let Some(hit) = objects[index].hit(ray, &range) {
+                range.max = hit.t();
+                closest = Some((index, hit));
+            }
+        }
+
+        // hits are only accepted when closer than the previous one, so the last one is the closest
+        let mut hit_index = 0;
+        let hit = self.tlas.bvh.hit(ray, &range, |index, range| {
+            let hit = objects[self.tlas.bounded[index]].hit(ray, range);
+            if hit.is_some() {
+                hit_index = self.tlas.bounded[index];
+            }
+            hit
+        });
+        hit.map(|hit| (hit_index, hit)).or(closest)
     }
-    if d.z < 0. {
-        swap(&mut mn[2], &mut mx[2]);
+
+    /// Like `hit`, but also counts the nodes visited by all hierarchies on the way, nested ones included.
+    pub fn hit_counted(&self, ray: &Ray, range: &Accuracy) -> (Option<HitRecord<'a>>, usize) {
+        let Scene { objects, .. } = self.scene;
+        let mut range = Accuracy { min: range.min, max: range.max };
+
+        let mut closest = None;
+        let mut steps = 0;
+        for &index in &self.tlas.unbounded {
+            let (hit, object_steps) = objects[index].hit_counted(ray, &range);
+            steps += object_steps;
+            if let Some(hit) = hit {
+                range.max = hit.t();
+                closest = Some(hit);
+            }
+        }
+
+        let (hit, bvh_steps) = self.tlas.bvh.hit_counted(ray, &range, |index, range| {
+            let (hit, object_steps) = objects[self.tlas.bounded[index]].hit_counted(ray, range);
+            steps += object_steps;
+            hit
+        });
+        (hit.or(closest), steps + bvh_steps)
     }
 
-    let mn = mn.iter().max_by(|a, b| if a < b { Less } else { Greater }).unwrap();
-    let mx = mx.iter().min_by(|a, b| if a <= b { Less } else { Greater }).unwrap();
+    /// Share of light passing along the ray within `range`, the product over all objects on the way.
+    pub fn transmittance(&self, ray: &Ray, range: &Accuracy) -> f32 {
+        let Scene { objects, .. } = self.scene;
+        let mut transmittance = self.tlas.unbounded.iter()
+            .map(|index| objects[*index].transmittance(ray, range))
+            .product::<f32>();
+        if transmittance == 0. {
+            return 0.;
+        }
+        self.tlas.bvh.hit(ray, range, |index, range| {
+            transmittance *= objects[self.tlas.bounded[index]].transmittance(ray, range);
+            if transmittance == 0. {
+                // nothing gets through anymore, so the rest of the tree doesn't matter
+                range.max = f32::NEG_INFINITY;
+            }
+            None
+        });
+        transmittance
+    }
+}
 
-    0. <= *mn && mn <= mx
+impl Hittable for BVHScene<'_> {
+    fn hit(&self, ray: &Ray, range: &Accuracy) -> Option<HitRecord<'_>> {
+        self.hit_object(ray, range).map(|(_, hit)| hit)
+    }
 }
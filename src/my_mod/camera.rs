@@ -2,6 +2,7 @@ use glam::Vec3;
 
 use crate::my_mod::angle::Angle;
 use crate::my_mod::ray::Ray;
+use crate::my_mod::time::Shutter;
 use crate::my_mod::utils::random_in_unit_disk;
 
 #[derive(Debug)]
@@ -13,6 +14,7 @@ pub struct Camera {
     lens_radius: f32,
     x: Vec3,
     y: Vec3,
+    shutter: Shutter,
 }
 
 impl Camera {
@@ -50,9 +52,26 @@ impl Camera {
             lens_radius,
             x,
             y,
+            shutter: Shutter::default(),
         }
     }
 
+    /// Stretches the viewport horizontally to a new aspect ratio, the vertical field of view is kept.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        let center = self.lower_left_corner + self.horizontal / 2. + self.vertical / 2.;
+        self.horizontal = aspect_ratio * self.vertical.length() * self.x;
+        self.lower_left_corner = center - self.horizontal / 2. - self.vertical / 2.;
+    }
+
+    /// Spreads the rays over the shutter interval, an instant shutter by default.
+    pub fn set_shutter(&mut self, shutter: Shutter) {
+        self.shutter = shutter;
+    }
+
+    pub fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+
     pub fn get_ray(&self, u: f32, v: f32) -> Ray {
         let (rd_x, rd_y) = random_in_unit_disk();
         let rd_x = self.lens_radius * rd_x;
@@ -61,6 +80,6 @@ impl Camera {
         let origin = self.origin + offset;
         let direction =
             self.lower_left_corner + u * self.horizontal + v * self.vertical - origin;
-        Ray::new(&origin, &direction)
+        Ray::new(&origin, &direction, self.shutter.sample())
     }
 }
@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::my_mod::bvh::SplitStrategy;
+use crate::my_mod::integrator::IntegratorKind;
+use crate::my_mod::resolution::CropWindow;
+use crate::my_mod::tonemap::ToneMap;
+use crate::my_mod::writer::ImageFormat;
+
+/// Renders a scene with a path tracer.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Args {
+    /// JSON scene description, the built-in random scene is rendered if omitted
+    pub scene: Option<PathBuf>,
+
+    /// Output image path
+    #[arg(short, long, default_value = "image.ppm")]
+    pub output: PathBuf,
+
+    /// Output format: ppm, ppm-binary, png, png16, exr, hdr or pfm, guessed from the output extension if omitted
+    #[arg(short, long)]
+    pub format: Option<ImageFormat>,
+
+    /// Write the share of samples hitting geometry as an alpha channel, PNG and EXR only
+    #[arg(long)]
+    pub alpha: bool,
+
+    /// Tone mapping of PNG and PPM output: clamp, reinhard, reinhard-extended, aces or hable
+    #[arg(long, default_value = "clamp")]
+    pub tonemap: ToneMap,
+
+    /// Exposure adjustment in stops applied before tone mapping
+    #[arg(long, default_value_t = 0., allow_negative_numbers = true)]
+    pub exposure: f32,
+
+    /// Exposed radiance mapped to white by reinhard-extended and hable
+    #[arg(long, default_value_t = 11.2)]
+    pub white_point: f32,
+
+    /// Image width in pixels, the height follows the scene aspect ratio unless given too
+    #[arg(long)]
+    pub width: Option<usize>,
+
+    /// Image height in pixels, the width follows the scene aspect ratio unless given too
+    #[arg(long)]
+    pub height: Option<usize>,
+
+    /// Samples per pixel
+    #[arg(short, long)]
+    pub spp: Option<usize>,
+
+    /// Maximum number of ray bounces
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<usize>,
+
+    /// Number of worker threads, all cores are used by default
+    #[arg(short = 'j', long)]
+    pub threads: Option<usize>,
+
+    /// BVH split strategy: sah, median or random
+    #[arg(long)]
+    pub bvh: Option<SplitStrategy>,
+
+    /// Render path traced light or a debugging view: ao, normals, depth, albedo, uv, bvh-cost or material-id
+    #[arg(long)]
+    pub integrator: Option<IntegratorKind>,
+
+    /// Find emissive objects only by chance instead of sampling them at every diffuse bounce
+    #[arg(long)]
+    pub no_light_sampling: bool,
+
+    /// Seed for a reproducible render
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Render only the pixel window X0,Y0,X1,Y1 (exclusive end, origin at the top-left)
+    #[arg(long, value_parser = parse_crop)]
+    pub crop: Option<CropWindow>,
+
+    /// Number of frames to render of the instance keyframes, numbered in the output file names when more than one
+    #[arg(long, default_value_t = 1)]
+    pub frames: usize,
+
+    /// Print nothing but errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print scene statistics and timings
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+fn parse_crop(value: &str) -> Result<CropWindow, String> {
+    let bounds = value.split(',')
+        .map(|x| x.trim().parse::<usize>().map_err(|_| format!("invalid number '{}'", x)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match bounds[..] {
+        [x0, y0, x1, y1] if x0 < x1 && y0 < y1 => Ok(CropWindow { x0, y0, x1, y1 }),
+        [_, _, _, _] => Err("window must not be empty".to_string()),
+        _ => Err("expected X0,Y0,X1,Y1".to_string()),
+    }
+}
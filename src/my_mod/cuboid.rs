@@ -0,0 +1,107 @@
+use glam::Vec3;
+
+use crate::my_mod::bbox::BBox;
+use crate::my_mod::bvh::Boundable;
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::material::Material;
+use crate::my_mod::quad::Quad;
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::{LightSample, SceneObject};
+use crate::my_mod::time::{Shutter, TimePoint};
+use crate::my_mod::utils::random;
+
+/// An axis-aligned box made of six outward facing quads.
+#[derive(Debug)]
+pub struct Cuboid {
+    min: Vec3,
+    max: Vec3,
+    faces: [Quad; 6],
+}
+
+impl Cuboid {
+    pub fn new(a: Vec3, b: Vec3, material: Material) -> Cuboid {
+        let min = a.min(b);
+        let max = a.max(b);
+        let extent = max - min;
+        assert!(extent.min_element() > 0., "cuboid must not be flat");
+
+        let dx = Vec3::new(extent.x, 0., 0.);
+        let dy = Vec3::new(0., extent.y, 0.);
+        let dz = Vec3::new(0., 0., extent.z);
+        let face = |corner: Vec3, u: Vec3, v: Vec3| Quad::new(corner, u, v, material.clone());
+
+        let faces = [
+            face(Vec3::new(min.x, min.y, max.z), dx, dy),
+            face(Vec3::new(max.x, min.y, min.z), -dx, dy),
+            face(Vec3::new(max.x, min.y, max.z), -dz, dy),
+            face(Vec3::new(min.x, min.y, min.z), dz, dy),
+            face(Vec3::new(min.x, max.y, max.z), dx, -dz),
+            face(Vec3::new(min.x, min.y, min.z), dx, dz),
+        ];
+
+        Cuboid { min, max, faces }
+    }
+
+    fn total_area(&self) -> f32 {
+        self.faces.iter().map(|face| face.area()).sum()
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        let mut range = Accuracy { min: accuracy.min, max: accuracy.max };
+        let mut closest = None;
+        for face in &self.faces {
+            if let Some(hit) = face.hit(ray, &range) {
+                range.max = hit.t();
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+}
+
+impl Boundable for Cuboid {
+    fn bbox(&self, _shutter: &Shutter) -> BBox {
+        BBox {
+            min: self.min,
+            max: self.max,
+        }
+    }
+}
+
+impl SceneObject for Cuboid {
+    fn is_emissive(&self) -> bool {
+        self.faces[0].is_emissive()
+    }
+
+    /// Picks a face by its share of the surface.
+    fn sample_towards(&self, origin: Vec3, time: TimePoint) -> Option<LightSample> {
+        let total_area = self.total_area();
+        let mut target = random::<f32>() * total_area;
+        let face = self.faces.iter()
+            .find(|face| {
+                target -= face.area();
+                target <= 0.
+            })
+            .unwrap_or(&self.faces[5]);
+
+        let sample = face.sample_towards(origin, time)?;
+        // a point on a far face is hidden by a near one, which is what is seen along the direction
+        let ray = Ray::new(&origin, &sample.direction, time);
+        let nearest = self.hit(&ray, &Accuracy { min: 1e-4, max: f32::INFINITY })?;
+        Some(LightSample {
+            distance: nearest.t(),
+            pdf: self.pdf_towards(origin, sample.direction, time),
+            ..sample
+        })
+    }
+
+    fn pdf_towards(&self, origin: Vec3, direction: Vec3, time: TimePoint) -> f32 {
+        // a direction may pass through two faces, either could have been sampled
+        let total_area = self.total_area();
+        self.faces.iter()
+            .map(|face| face.area() / total_area * face.pdf_towards(origin, direction, time))
+            .sum()
+    }
+}
@@ -0,0 +1,126 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3};
+
+use crate::my_mod::bbox::BBox;
+use crate::my_mod::bvh::Boundable;
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::material::Material;
+use crate::my_mod::plane::{face_normal, intersect_plane};
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::{LightSample, SceneObject};
+use crate::my_mod::time::{Shutter, TimePoint};
+use crate::my_mod::utils::random;
+use crate::my_mod::vec3;
+
+/// A flat disk around `center`, facing `normal`.
+#[derive(Debug)]
+pub struct Disk {
+    center: Vec3,
+    normal: Vec3,
+    radius: f32,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Material,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f32, material: Material) -> Disk {
+        assert!(normal.length() > 0.);
+        assert!(radius > 0.);
+        let normal = normal.normalize();
+        let (tangent, bitangent) = vec3::orthonormal_basis(normal);
+        Disk {
+            center,
+            normal,
+            radius,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+
+    fn area_pdf(&self, direction: Vec3, distance: f32) -> f32 {
+        let cos = Vec3::dot(self.normal, direction).abs();
+        if cos < 1e-6 {
+            return 0.;
+        }
+        distance.powi(2) / (cos * PI * self.radius.powi(2))
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        let t = intersect_plane(ray, self.center, self.normal, accuracy)?;
+        let point = ray.at(t);
+
+        let offset = point - self.center;
+        let distance_squared = offset.length_squared();
+        if distance_squared > self.radius.powi(2) {
+            return None;
+        }
+
+        // angle around the normal and distance from the center
+        let x = Vec3::dot(offset, self.tangent);
+        let y = Vec3::dot(offset, self.bitangent);
+        let uv = Vec2::new(
+            (f32::atan2(y, x) + PI) / (2. * PI),
+            distance_squared.sqrt() / self.radius,
+        );
+
+        let dpdu = x * self.bitangent - y * self.tangent;
+
+        let (front, normal) = face_normal(ray, self.normal);
+        Some(HitRecord::new(point, normal, uv, &self.material, t, front).dpdu(dpdu))
+    }
+}
+
+impl Boundable for Disk {
+    fn bbox(&self, _shutter: &Shutter) -> BBox {
+        // along each axis the rim reaches radius times the sine between the axis and the normal
+        let sin_squared = (Vec3::ONE - self.normal * self.normal).max(Vec3::ZERO);
+        let extent = self.radius * Vec3::new(sin_squared.x.sqrt(), sin_squared.y.sqrt(), sin_squared.z.sqrt())
+            + Vec3::splat(1e-4);
+        BBox {
+            min: self.center - extent,
+            max: self.center + extent,
+        }
+    }
+}
+
+impl SceneObject for Disk {
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_towards(&self, origin: Vec3, _time: TimePoint) -> Option<LightSample> {
+        let r = self.radius * random::<f32>().sqrt();
+        let phi = 2. * PI * random::<f32>();
+        let point = self.center + r * (phi.cos() * self.tangent + phi.sin() * self.bitangent);
+
+        let to_point = point - origin;
+        let distance = to_point.length();
+        if distance == 0. {
+            return None;
+        }
+        let direction = to_point / distance;
+        let pdf = self.area_pdf(direction, distance);
+        if pdf == 0. {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            pdf,
+        })
+    }
+
+    fn pdf_towards(&self, origin: Vec3, direction: Vec3, time: TimePoint) -> f32 {
+        let ray = Ray::new(&origin, &direction, time);
+        match self.hit(&ray, &Accuracy { min: 1e-4, max: f32::INFINITY }) {
+            Some(hit) => self.area_pdf(ray.direction(), hit.t()),
+            None => 0.,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::f32::consts::PI;
+
+use glam::Vec3;
+
+use crate::my_mod::hittable::HitRecord;
+use crate::my_mod::texture::Texture;
+
+/// Light given off by a surface, added to whatever its material scatters.
+///
+/// The radiance is the color times `strength`, the same in every direction. One-sided emitters
+/// only light the side their normal faces.
+#[derive(Debug, Clone)]
+pub struct Emission {
+    color: Texture,
+    strength: f32,
+    two_sided: bool,
+    /// `strength` is the power of the whole shape until `spread_over` knows its area.
+    power: bool,
+}
+
+impl Emission {
+    pub fn new(color: impl Into<Texture>, strength: f32) -> Emission {
+        assert!(strength >= 0., "emission strength must not be negative");
+        Emission {
+            color: color.into(),
+            strength,
+            two_sided: false,
+            power: false,
+        }
+    }
+
+    pub fn two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    /// Takes `strength` as the total power emitted, so shapes of any size give off the same light.
+    pub fn power(mut self) -> Self {
+        self.power = true;
+        self
+    }
+
+    pub fn is_power(&self) -> bool {
+        self.power
+    }
+
+    /// Turns a power into the radiance of a shape with the given surface area.
+    pub fn spread_over(mut self, area: f32) -> Self {
+        if self.power {
+            assert!(area > 0., "emitting area must be positive");
+            let sides = if self.two_sided { 2. } else { 1. };
+            // a diffuse emitter of radiance L gives off π L per unit of area and side
+            self.strength /= PI * area * sides;
+            self.power = false;
+        }
+        self
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.strength == 0.
+    }
+
+    /// Radiance leaving the hit point back along the ray.
+    pub fn radiance(&self, hit_record: &HitRecord) -> Vec3 {
+        if self.is_black() || !(self.two_sided || hit_record.front()) {
+            return Vec3::ZERO;
+        }
+        self.strength * Vec3::from(self.color.value(hit_record.uv(), hit_record.point()))
+    }
+}
@@ -0,0 +1,295 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use glam::Vec3;
+
+use crate::my_mod::angle::Angle;
+use crate::my_mod::utils::random;
+
+/// Light arriving from infinitely far away, read from an equirectangular image.
+///
+/// The columns span all longitudes like the texture coordinates of `Sphere`, the top row looks
+/// straight up and the bottom one straight down. Texels are constant over their area, and directions
+/// are sampled in proportion to their luminance so small bright regions like the sun are found by
+/// light sampling.
+#[derive(Debug)]
+pub struct Environment {
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3>,
+    /// About the y axis, counterclockwise seen from above.
+    rotation: f32,
+    scale: f32,
+    /// Picks a row, then a column within it.
+    rows: Distribution,
+    columns: Vec<Distribution>,
+}
+
+impl Environment {
+    /// `texels` is linear radiance, row by row from the top.
+    pub fn new(width: usize, height: usize, texels: Vec<Vec3>) -> Environment {
+        assert!(width > 0 && height > 0);
+        assert_eq!(width * height, texels.len());
+        assert!(texels.iter().all(|t| t.is_finite() && t.min_element() >= 0.), "radiance must be finite and not negative");
+
+        let luminance = |texel: Vec3| Vec3::dot(texel, Vec3::new(0.2126, 0.7152, 0.0722));
+        let columns = texels.chunks(width)
+            .map(|row| Distribution::new(row.iter().map(|t| luminance(*t)).collect()))
+            .collect::<Vec<_>>();
+        // rows near the poles cover less solid angle
+        let rows = Distribution::new(columns.iter().enumerate()
+            .map(|(row, distribution)| distribution.total * (PI * (row as f32 + 0.5) / height as f32).sin())
+            .collect());
+
+        Environment {
+            width,
+            height,
+            texels,
+            rotation: 0.,
+            scale: 1.,
+            rows,
+            columns,
+        }
+    }
+
+    /// Loads a Radiance `.hdr` or an OpenEXR `.exr` image.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Environment> {
+        let path = path.as_ref();
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let (width, height, texels) = match extension.as_deref() {
+            Some("hdr") => read_rgbe(path)?,
+            Some("exr") => read_exr(path)?,
+            _ => return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: unsupported environment format, expected .hdr or .exr", path.display()),
+            )),
+        };
+        if texels.iter().any(|t| !t.is_finite() || t.min_element() < 0.) {
+            return Err(invalid_data(path, "radiance must be finite and not negative"));
+        }
+        Ok(Environment::new(width, height, texels))
+    }
+
+    pub fn rotation(mut self, rotation: Angle) -> Self {
+        self.rotation = rotation.radians();
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        assert!(scale >= 0., "environment scale must not be negative");
+        self.scale = scale;
+        self
+    }
+
+    /// Radiance arriving from `direction`, which needn't be a unit vector.
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let (column, row) = self.texel(direction);
+        self.scale * self.texels[row * self.width + column]
+    }
+
+    /// Picks a unit direction with the density of `pdf`.
+    pub fn sample(&self) -> Option<(Vec3, f32)> {
+        let (row, v) = self.rows.sample();
+        let (column, u) = self.columns[row].sample();
+        let local = direction_at(self.width, self.height, column as f32 + u, row as f32 + v);
+        let sin_theta = (1. - local.y * local.y).max(0.).sqrt();
+        if sin_theta <= 0. {
+            return None;
+        }
+
+        let probability = self.rows.probability(row) * self.columns[row].probability(column);
+        Some((self.rotate(local, self.rotation), self.density(probability, sin_theta)))
+    }
+
+    /// Solid angle density with which `sample` returns `direction`.
+    pub fn pdf(&self, direction: Vec3) -> f32 {
+        let (column, row) = self.texel(direction);
+        let local = self.rotate(direction.normalize(), -self.rotation);
+        let sin_theta = (1. - local.y * local.y).max(0.).sqrt();
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        let probability = self.rows.probability(row) * self.columns[row].probability(column);
+        self.density(probability, sin_theta)
+    }
+
+    /// Converts the probability of a texel to a density over the solid angle at `sin_theta`.
+    fn density(&self, probability: f32, sin_theta: f32) -> f32 {
+        probability * (self.width * self.height) as f32 / (2. * PI * PI * sin_theta)
+    }
+
+    /// Column and row of the texel seen along `direction`.
+    fn texel(&self, direction: Vec3) -> (usize, usize) {
+        let local = self.rotate(direction.normalize(), -self.rotation);
+        let u = (f32::atan2(-local.z, local.x) + PI) / (2. * PI);
+        let v = f32::acos(local.y.clamp(-1., 1.)) / PI;
+        let column = ((u * self.width as f32) as usize).min(self.width - 1);
+        let row = ((v * self.height as f32) as usize).min(self.height - 1);
+        (column, row)
+    }
+
+    fn rotate(&self, v: Vec3, angle: f32) -> Vec3 {
+        let (sin, cos) = angle.sin_cos();
+        Vec3::new(cos * v.x + sin * v.z, v.y, cos * v.z - sin * v.x)
+    }
+}
+
+/// Unit direction through the point `(x, y)` of an equirectangular image, in texels from the top-left corner.
+pub fn direction_at(width: usize, height: usize, x: f32, y: f32) -> Vec3 {
+    let theta = PI * y / height as f32;
+    let phi = 2. * PI * x / width as f32;
+    let (sin_theta, cos_theta) = theta.sin_cos();
+    Vec3::new(-sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin())
+}
+
+/// Piecewise constant distribution over `0..n`, with a continuous offset within each piece.
+#[derive(Debug)]
+struct Distribution {
+    /// `n + 1` partial sums from 0 to 1.
+    cdf: Vec<f32>,
+    total: f32,
+}
+
+impl Distribution {
+    /// All zero weights are sampled uniformly.
+    fn new(weights: Vec<f32>) -> Distribution {
+        let total = weights.iter().sum::<f32>();
+        let n = weights.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.);
+        let mut sum = 0.;
+        for (i, weight) in weights.into_iter().enumerate() {
+            // summed in the same order as the total, so the last one is exactly 1
+            sum += weight;
+            cdf.push(if total > 0. { sum / total } else { (i + 1) as f32 / n as f32 });
+        }
+        Distribution { cdf, total }
+    }
+
+    /// A piece and the position within it in `[0, 1)`.
+    fn sample(&self) -> (usize, f32) {
+        let xi = random::<f32>();
+        // the first piece ending past xi has a positive probability
+        let index = (self.cdf.partition_point(|c| *c <= xi) - 1).min(self.cdf.len() - 2);
+        let probability = self.probability(index);
+        let offset = if probability > 0. { (xi - self.cdf[index]) / probability } else { 0.5 };
+        (index, offset.clamp(0., 1. - f32::EPSILON))
+    }
+
+    fn probability(&self, index: usize) -> f32 {
+        self.cdf[index + 1] - self.cdf[index]
+    }
+}
+
+fn invalid_data(path: &Path, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message))
+}
+
+/// Reads a Radiance `.hdr` file with flat or run-length encoded scanlines in the usual `-Y h +X w` order.
+fn read_rgbe(path: &Path) -> io::Result<(usize, usize, Vec<Vec3>)> {
+    let bytes = fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let invalid = |message: &str| invalid_data(path, message);
+
+    let mut lines = bytes.split(|b| *b == b'\n');
+    let mut offset = 0;
+    let mut next_line = || {
+        let line = lines.next()?;
+        offset += line.len() + 1;
+        Some(String::from_utf8_lossy(line).into_owned())
+    };
+    if !next_line().is_some_and(|magic| magic.starts_with("#?")) {
+        return Err(invalid("not a Radiance .hdr image"));
+    }
+    loop {
+        match next_line() {
+            Some(line) if line.is_empty() => break,
+            Some(line) if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" => {
+                return Err(invalid("only RGBE pixels are supported"));
+            }
+            Some(_) => {}
+            None => return Err(invalid("unexpected end of header")),
+        }
+    }
+    let resolution = next_line().ok_or_else(|| invalid("missing resolution"))?;
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => match (height.parse::<usize>(), width.parse::<usize>()) {
+            (Ok(height), Ok(width)) if height > 0 && width > 0 => (height, width),
+            _ => return Err(invalid("invalid resolution")),
+        },
+        _ => return Err(invalid("only -Y h +X w scanline order is supported")),
+    };
+
+    let mut data = bytes.get(offset..).unwrap_or_default().iter().copied();
+    let mut texels = Vec::with_capacity(width * height);
+    let mut scanline = vec![[0u8; 4]; width];
+    let mut planes = vec![0u8; 4 * width];
+    for _ in 0..height {
+        let start = [data.next(), data.next(), data.next(), data.next()];
+        let [Some(a), Some(b), Some(c), Some(d)] = start else {
+            return Err(invalid("truncated pixel data"));
+        };
+        if a == 2 && b == 2 && (8..32768).contains(&width) && (((c as usize) << 8) | d as usize) == width {
+            // each channel run-length encoded on its own
+            for plane in planes.chunks_mut(width) {
+                let mut x = 0;
+                while x < width {
+                    let count = data.next().ok_or_else(|| invalid("truncated pixel data"))? as usize;
+                    let (run, literal) = if count > 128 { (count - 128, false) } else { (count, true) };
+                    if run == 0 || x + run > width {
+                        return Err(invalid("corrupt run-length encoding"));
+                    }
+                    let mut value = 0;
+                    for i in 0..run {
+                        if literal || i == 0 {
+                            value = data.next().ok_or_else(|| invalid("truncated pixel data"))?;
+                        }
+                        plane[x + i] = value;
+                    }
+                    x += run;
+                }
+            }
+            for (x, pixel) in scanline.iter_mut().enumerate() {
+                *pixel = [planes[x], planes[width + x], planes[2 * width + x], planes[3 * width + x]];
+            }
+        } else {
+            scanline[0] = [a, b, c, d];
+            for pixel in scanline.iter_mut().skip(1) {
+                for byte in pixel.iter_mut() {
+                    *byte = data.next().ok_or_else(|| invalid("truncated pixel data"))?;
+                }
+            }
+        }
+        texels.extend(scanline.iter().map(|[r, g, b, e]| from_rgbe(*r, *g, *b, *e)));
+    }
+    Ok((width, height, texels))
+}
+
+fn from_rgbe(r: u8, g: u8, b: u8, exponent: u8) -> Vec3 {
+    if exponent == 0 {
+        return Vec3::ZERO;
+    }
+    Vec3::new(r as f32, g as f32, b as f32) * 2f32.powi(exponent as i32 - (128 + 8))
+}
+
+/// Reads the first RGB(A) layer of an OpenEXR file, the alpha channel is ignored.
+fn read_exr(path: &Path) -> io::Result<(usize, usize, Vec<Vec3>)> {
+    use exr::prelude::{read_first_rgba_layer_from_file, RgbaChannels, Vec2};
+
+    let image = read_first_rgba_layer_from_file(
+        path,
+        |resolution: Vec2<usize>, _: &RgbaChannels| {
+            (resolution.width(), vec![Vec3::ZERO; resolution.width() * resolution.height()])
+        },
+        |(width, texels): &mut (usize, Vec<Vec3>), position: Vec2<usize>, (r, g, b, _): (f32, f32, f32, f32)| {
+            texels[position.y() * *width + position.x()] = Vec3::new(r, g, b);
+        },
+    ).map_err(|e| invalid_data(path, &e.to_string()))?;
+
+    let (width, texels) = image.layer_data.channel_data.pixels;
+    let height = texels.len() / width;
+    Ok((width, height, texels))
+}
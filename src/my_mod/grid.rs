@@ -0,0 +1,118 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use glam::Vec3;
+
+use crate::my_mod::bbox::BBox;
+
+/// Density samples on a regular lattice spanning a box, with trilinear lookup in between.
+#[derive(Debug)]
+pub struct DensityGrid {
+    resolution: [usize; 3],
+    values: Vec<f32>,
+    bbox: BBox,
+    max: f32,
+}
+
+impl DensityGrid {
+    /// `values` has x varying fastest and z slowest, the outer samples lie on the faces of `bbox`.
+    pub fn new(resolution: [usize; 3], values: Vec<f32>, bbox: BBox) -> DensityGrid {
+        assert!(resolution.iter().all(|n| *n >= 2), "grid needs two samples along every axis");
+        assert_eq!(resolution.iter().product::<usize>(), values.len());
+        assert!(bbox.min.cmplt(bbox.max).all(), "grid box must not be flat");
+        assert!(values.iter().all(|v| *v >= 0.), "densities must not be negative");
+        let max = values.iter().copied().fold(0., f32::max);
+        DensityGrid {
+            resolution,
+            values,
+            bbox,
+            max,
+        }
+    }
+
+    /// Loads a dense single channel float32 grid in the Mitsuba `.vol` format:
+    /// `VOL`, version 3, encoding 1, the x, y and z resolutions, the channel count 1 and the
+    /// bounding box as min and max, all little-endian, followed by the values.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<DensityGrid> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+
+        const HEADER: usize = 4 + 5 * 4 + 6 * 4;
+        if bytes.len() < HEADER || &bytes[..3] != b"VOL" {
+            return Err(invalid("not a .vol grid"));
+        }
+        if bytes[3] != 3 {
+            return Err(invalid("unsupported .vol version, expected 3"));
+        }
+        let word = |i: usize| [bytes[4 + 4 * i], bytes[5 + 4 * i], bytes[6 + 4 * i], bytes[7 + 4 * i]];
+        let int = |i: usize| i32::from_le_bytes(word(i));
+        let float = |i: usize| f32::from_le_bytes(word(i));
+
+        if int(0) != 1 {
+            return Err(invalid("unsupported encoding, expected float32"));
+        }
+        if int(4) != 1 {
+            return Err(invalid("only single channel grids are supported"));
+        }
+        let resolution = [int(1), int(2), int(3)];
+        if resolution.iter().any(|n| *n < 2) {
+            return Err(invalid("grid needs two samples along every axis"));
+        }
+        let resolution = resolution.map(|n| n as usize);
+        let bbox = BBox {
+            min: Vec3::new(float(5), float(6), float(7)),
+            max: Vec3::new(float(8), float(9), float(10)),
+        };
+        if !bbox.min.cmplt(bbox.max).all() {
+            return Err(invalid("grid box must not be flat"));
+        }
+
+        // the size of the file must not overflow either
+        let count = resolution.iter()
+            .try_fold(1, |count: usize, n| count.checked_mul(*n))
+            .filter(|count| *count <= (usize::MAX - HEADER) / 4)
+            .ok_or_else(|| invalid("grid resolution is too large"))?;
+        if bytes.len() != HEADER + 4 * count {
+            return Err(invalid(&format!("expected {} values", count)));
+        }
+        let values = bytes[HEADER..]
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect::<Vec<_>>();
+        if values.iter().any(|v| !v.is_finite() || *v < 0.) {
+            return Err(invalid("densities must be finite and not negative"));
+        }
+        Ok(DensityGrid::new(resolution, values, bbox))
+    }
+
+    pub fn bbox(&self) -> BBox {
+        self.bbox
+    }
+
+    /// The largest density, which bounds every lookup.
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    /// Trilinearly interpolated density at `point`, zero outside the box.
+    pub fn density(&self, point: Vec3) -> f32 {
+        if !self.bbox.contains(&point) {
+            return 0.;
+        }
+        let [nx, ny, nz] = self.resolution;
+        let cells = Vec3::new((nx - 1) as f32, (ny - 1) as f32, (nz - 1) as f32);
+        let position = (point - self.bbox.min) / self.bbox.extent() * cells;
+        // the lower corner of the cell, the upper boundary belongs to the last cell
+        let corner = position.floor().min(cells - Vec3::ONE);
+        let fraction = position - corner;
+        let (x, y, z) = (corner.x as usize, corner.y as usize, corner.z as usize);
+
+        let value = |dx: usize, dy: usize, dz: usize| self.values[((z + dz) * ny + y + dy) * nx + x + dx];
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let along_x = |dy: usize, dz: usize| lerp(value(0, dy, dz), value(1, dy, dz), fraction.x);
+        let along_y = |dz: usize| lerp(along_x(0, dz), along_x(1, dz), fraction.y);
+        lerp(along_y(0), along_y(1), fraction.z)
+    }
+}
@@ -1,14 +1,18 @@
 use std::option::Option;
 
-use glam::Vec3;
+use glam::{Vec2, Vec3};
 
 use crate::my_mod::material::Material;
 use crate::my_mod::ray::Ray;
+use crate::my_mod::vec3;
 
 #[derive(Debug, Clone)]
 pub struct HitRecord<'a> {
     point: Vec3,
     normal: Vec3,
+    /// Unit vector perpendicular to the normal, along which `u` grows.
+    tangent: Vec3,
+    uv: Vec2,
     material: &'a Material,
     t: f32,
     front: bool,
@@ -18,6 +22,7 @@ impl<'a> HitRecord<'a> {
     pub fn new(
         point: Vec3,
         normal: Vec3,
+        uv: Vec2,
         material: &'a Material,
         t: f32,
         front: bool,
@@ -25,12 +30,24 @@ impl<'a> HitRecord<'a> {
         Self {
             point,
             normal,
+            tangent: vec3::orthonormal_basis(normal).0,
+            uv,
             material,
             t,
             front,
         }
     }
 
+    /// Orients the tangent along `dpdu`, the change of the point with `u`. Keeps an arbitrary one
+    /// where `dpdu` is zero or along the normal.
+    pub fn dpdu(mut self, dpdu: Vec3) -> Self {
+        let tangent = dpdu - Vec3::dot(dpdu, self.normal) * self.normal;
+        if tangent.length_squared() > 1e-12 {
+            self.tangent = tangent.normalize();
+        }
+        self
+    }
+
     pub fn point(&self) -> Vec3 {
         self.point
     }
@@ -39,11 +56,20 @@ impl<'a> HitRecord<'a> {
         self.normal
     }
 
+    pub fn tangent(&self) -> Vec3 {
+        self.tangent
+    }
+
+    /// Surface parametrization at the hit point, used for texture lookups.
+    pub fn uv(&self) -> Vec2 {
+        self.uv
+    }
+
     pub fn front(&self) -> bool {
         self.front
     }
 
-    pub fn material(&self) -> &Material {
+    pub fn material(&self) -> &'a Material {
         self.material
     }
 
@@ -58,5 +84,5 @@ pub struct Accuracy {
 }
 
 pub trait Hittable {
-    fn hit(&self, ray: &Ray, range: &Accuracy) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, range: &Accuracy) -> Option<HitRecord<'_>>;
 }
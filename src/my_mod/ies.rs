@@ -0,0 +1,126 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Intensity distribution of a luminaire, read from an IES LM-63 photometric file of type C.
+///
+/// Vertical angles are measured from the nadir, horizontal ones around it. Values are relative to the
+/// brightest direction, so the profile only shapes the light and its owner keeps setting the intensity.
+#[derive(Debug)]
+pub struct IesProfile {
+    /// Ascending, in degrees.
+    vertical: Vec<f32>,
+    /// Ascending from 0, in degrees. The last one tells the symmetry of the luminaire.
+    horizontal: Vec<f32>,
+    /// All vertical angles of the first horizontal angle, then the next one.
+    values: Vec<f32>,
+}
+
+impl IesProfile {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<IesProfile> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        IesProfile::parse(&String::from_utf8_lossy(&bytes))
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message)))
+    }
+
+    fn parse(text: &str) -> Result<IesProfile, String> {
+        let mut lines = text.lines();
+        // keywords like [MANUFAC] come first, the photometric data follows the tilt line
+        let tilt = lines.by_ref()
+            .map(str::trim)
+            .find_map(|line| line.strip_prefix("TILT="))
+            .ok_or("missing TILT line")?;
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f32>().map_err(|_| format!("invalid number {:?}", token)));
+        let mut next = || numbers.next().unwrap_or_else(|| Err("unexpected end of data".to_string()));
+
+        match tilt.trim() {
+            "NONE" => {}
+            "INCLUDE" => {
+                // lamp tilt doesn't matter for a fixed light, skip the geometry and the angle and factor lists
+                next()?;
+                let count = next()? as usize;
+                for _ in 0..2 * count {
+                    next()?;
+                }
+            }
+            _ => return Err("tilt data in a separate file is not supported".to_string()),
+        }
+
+        let _lamps = next()?;
+        let _lumens = next()?;
+        let _multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        if next()? != 1. {
+            return Err("only type C photometry is supported".to_string());
+        }
+        // units, width, length, height, ballast factor, future use, input watts
+        for _ in 0..7 {
+            next()?;
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err("no angles".to_string());
+        }
+
+        let vertical = (0..vertical_count).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+        let horizontal = (0..horizontal_count).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+        let mut values = (0..vertical_count * horizontal_count).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+
+        if vertical.windows(2).any(|w| w[0] >= w[1]) || vertical[0] < 0. || vertical[vertical_count - 1] > 180. {
+            return Err("vertical angles must be ascending within [0, 180]".to_string());
+        }
+        if horizontal.windows(2).any(|w| w[0] >= w[1]) || horizontal[0] != 0. || horizontal[horizontal_count - 1] > 360. {
+            return Err("horizontal angles must be ascending from 0 to at most 360".to_string());
+        }
+        if values.iter().any(|v| !v.is_finite() || *v < 0.) {
+            return Err("candela values must be finite and not negative".to_string());
+        }
+        let peak = values.iter().copied().fold(0., f32::max);
+        if peak == 0. {
+            return Err("the luminaire gives off no light".to_string());
+        }
+        values.iter_mut().for_each(|v| *v /= peak);
+
+        Ok(IesProfile { vertical, horizontal, values })
+    }
+
+    /// Relative intensity at `vertical` radians from the nadir and `horizontal` radians around it.
+    pub fn value(&self, vertical: f32, horizontal: f32) -> f32 {
+        let vertical = vertical.to_degrees();
+        if vertical < self.vertical[0] || vertical > self.vertical[self.vertical.len() - 1] {
+            return 0.;
+        }
+        // the last horizontal angle tells which part of the full turn the file covers
+        let mut horizontal = horizontal.to_degrees().rem_euclid(360.);
+        let last = self.horizontal[self.horizontal.len() - 1];
+        if last <= 180. && horizontal > 180. {
+            horizontal = 360. - horizontal;
+        }
+        if last <= 90. && horizontal > 90. {
+            horizontal = 180. - horizontal;
+        }
+
+        let (v, tv) = locate(&self.vertical, vertical);
+        let (h, th) = locate(&self.horizontal, horizontal);
+        let at = |h: usize, v: usize| self.values[h * self.vertical.len() + v];
+        let v1 = (v + 1).min(self.vertical.len() - 1);
+        let h1 = (h + 1).min(self.horizontal.len() - 1);
+        let near = at(h, v) * (1. - tv) + at(h, v1) * tv;
+        let far = at(h1, v) * (1. - tv) + at(h1, v1) * tv;
+        near * (1. - th) + far * th
+    }
+}
+
+/// The interval of the ascending `angles` containing `angle`, and the position within it.
+fn locate(angles: &[f32], angle: f32) -> (usize, f32) {
+    if angles.len() == 1 || angle <= angles[0] {
+        return (0, 0.);
+    }
+    let index = (angles.partition_point(|a| *a <= angle) - 1).min(angles.len() - 2);
+    let t = (angle - angles[index]) / (angles[index + 1] - angles[index]);
+    (index, t.clamp(0., 1.))
+}
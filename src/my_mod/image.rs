@@ -1,32 +1,83 @@
 use std::fmt::{Debug, Formatter};
-use std::io;
+
+use glam::Vec3;
 
 use crate::my_mod::resolution::Resolution;
 use crate::my_mod::rgb::RGB;
+use crate::my_mod::tonemap::DisplayTransform;
 
-pub struct Image {
+/// Pixels stored row by row, display-ready `RGB` by default.
+pub struct Image<P = RGB> {
     resolution: Resolution,
-    content: Vec<RGB>,
+    content: Vec<P>,
+    alpha: Option<Vec<f32>>,
 }
 
+/// Linear scene radiance, values are not limited to `[0, 1]`.
+pub type HdrImage = Image<Vec3>;
+
 impl Image {
     pub fn new(resolution: Resolution) -> Self {
         let pixels_count = resolution.width * resolution.height;
         Self {
             resolution,
             content: vec![RGB::black(); pixels_count],
+            alpha: None,
         }
     }
+}
+
+impl HdrImage {
+    /// Display colors are not premultiplied, partly covered pixels get the color of what covers them.
+    pub fn to_display(&self, transform: &DisplayTransform) -> Image {
+        let alpha = match &self.alpha {
+            Some(alpha) => alpha,
+            None => return self.map(|radiance| transform.apply(radiance)),
+        };
+        let content = self.content.iter()
+            .zip(alpha)
+            .map(|(radiance, alpha)| transform.apply(if *alpha > 0. { *radiance / *alpha } else { *radiance }))
+            .collect();
+        Image {
+            resolution: self.resolution,
+            content,
+            alpha: Some(alpha.clone()),
+        }
+    }
+}
 
-    pub fn new_with_content(resolution: Resolution, content: Vec<RGB>) -> Self {
+impl<P: Copy> Image<P> {
+    pub fn new_with_content(resolution: Resolution, content: Vec<P>) -> Self {
         let pixels_count = resolution.width * resolution.height;
         assert_eq!(pixels_count, content.len());
         Self {
             resolution,
-            content
+            content,
+            alpha: None,
         }
     }
 
+    /// Attaches per-pixel coverage in `[0, 1]`, stored row by row like the content. The content is
+    /// premultiplied by it.
+    pub fn with_alpha(mut self, alpha: Vec<f32>) -> Self {
+        assert_eq!(self.content.len(), alpha.len());
+        assert!(alpha.iter().all(|a| (0. ..=1.).contains(a)));
+        self.alpha = Some(alpha);
+        self
+    }
+
+    /// Coverage of the pixel, opaque if the image has no alpha channel.
+    pub fn alpha(&self, row: usize, column: usize) -> f32 {
+        match &self.alpha {
+            Some(alpha) => alpha[row * self.width() + column],
+            None => 1.,
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.alpha.is_some()
+    }
+
     pub fn width(&self) -> usize {
         self.resolution.width
     }
@@ -34,51 +85,35 @@ impl Image {
     pub fn height(&self) -> usize {
         self.resolution.height
     }
+
+    /// Converts every pixel, keeping the alpha channel.
+    pub fn map<Q>(&self, f: impl Fn(P) -> Q) -> Image<Q> {
+        Image {
+            resolution: self.resolution,
+            content: self.content.iter().map(|p| f(*p)).collect(),
+            alpha: self.alpha.clone(),
+        }
+    }
 }
 
-impl core::ops::Index<(usize, usize)> for Image {
-    type Output = RGB;
+impl<P: Copy> core::ops::Index<(usize, usize)> for Image<P> {
+    type Output = P;
 
     fn index(&self, index: (usize, usize)) -> &Self::Output {
         let (row, column) = index;
-        return &self.content[row * self.width() + column];
+        &self.content[row * self.width() + column]
     }
 }
 
-impl core::ops::IndexMut<(usize, usize)> for Image {
+impl<P: Copy> core::ops::IndexMut<(usize, usize)> for Image<P> {
     fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
         let (row, column) = index;
         let flat_index = row * self.width() + column;
-        return &mut self.content[flat_index];
-    }
-}
-
-impl super::ppm::SavableToPPM for Image {
-    fn save_to_ppm(&self, filename: &str) -> io::Result<()> {
-        use std::io::Write;
-
-        let mut f = std::fs::File::create(filename)?;
-
-        f.write(format!("P3\n").as_bytes())?;
-        f.write(format!("{} {}\n", self.width(), self.height()).as_bytes())?;
-        f.write(format!("255\n").as_bytes())?;
-
-        for i in 0..self.height() {
-            for j in 0..self.width() {
-                let rgb = self[(i, j)];
-                let r = (255. * rgb.r()) as i32;
-                let g = (255. * rgb.g()) as i32;
-                let b = (255. * rgb.b()) as i32;
-
-                f.write(format!("{} {} {}\n", r, g, b).as_bytes())?;
-            }
-        }
-
-        Ok(())
+        &mut self.content[flat_index]
     }
 }
 
-impl Debug for Image {
+impl<P> Debug for Image<P> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str(format!("Image [ resolution {:?} ]", self.resolution).as_str())
     }
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use glam::{Affine3A, Mat3A, Vec3};
+
+use crate::my_mod::bbox::BBox;
+use crate::my_mod::bvh::Boundable;
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::{LightSample, SceneObject};
+use crate::my_mod::time::{Shutter, TimePoint};
+
+/// A shared object placed in the world by an affine transform.
+///
+/// Rays are moved into the object's space for intersection and hits are moved back,
+/// so any number of instances can share one object's geometry and material.
+#[derive(Debug, Clone)]
+pub struct Instance {
+    object: Arc<dyn SceneObject>,
+    /// Object to world.
+    transform: Affine3A,
+    /// World to object.
+    inverse: Affine3A,
+    /// Transforms normals from object to world space, the inverse transpose of the linear part.
+    normal_matrix: Mat3A,
+    /// Transforms at frames of an animation, by increasing frame.
+    keyframes: Vec<(f32, Affine3A)>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn SceneObject>, transform: Affine3A) -> Instance {
+        let mut instance = Instance {
+            object,
+            transform: Affine3A::IDENTITY,
+            inverse: Affine3A::IDENTITY,
+            normal_matrix: Mat3A::IDENTITY,
+            keyframes: vec![],
+        };
+        instance.set_transform(transform);
+        instance
+    }
+
+    /// Animates the instance between transforms at increasing frames, interpolated as scale,
+    /// rotation and translation. It stands at the first one until `animate` says otherwise.
+    pub fn keyframes(mut self, keyframes: Vec<(f32, Affine3A)>) -> Self {
+        assert!(!keyframes.is_empty(), "an animation needs keyframes");
+        assert!(keyframes.windows(2).all(|pair| pair[0].0 < pair[1].0), "keyframes must be in increasing frames");
+        self.set_transform(keyframes[0].1);
+        self.keyframes = keyframes;
+        self
+    }
+
+    /// Moves the instance, the shared object stays as it is.
+    pub fn set_transform(&mut self, transform: Affine3A) {
+        assert!(transform.matrix3.determinant() != 0., "instance transform must be invertible");
+        self.transform = transform;
+        self.inverse = transform.inverse();
+        self.normal_matrix = self.inverse.matrix3.transpose();
+    }
+
+    /// The transform of the animation at `frame`, held before the first and after the last keyframe.
+    fn transform_at(&self, frame: f32) -> Affine3A {
+        let next = self.keyframes.partition_point(|(key, _)| *key <= frame);
+        if next == 0 {
+            return self.keyframes[0].1;
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].1;
+        }
+        let (frame0, transform0) = self.keyframes[next - 1];
+        let (frame1, transform1) = self.keyframes[next];
+        let t = (frame - frame0) / (frame1 - frame0);
+        let (scale0, rotation0, translation0) = transform0.to_scale_rotation_translation();
+        let (scale1, rotation1, translation1) = transform1.to_scale_rotation_translation();
+        Affine3A::from_scale_rotation_translation(
+            scale0.lerp(scale1, t),
+            rotation0.slerp(rotation1, t),
+            translation0.lerp(translation1, t),
+        )
+    }
+
+    /// The ray in object space, and how many object space units one world space unit along it covers.
+    fn to_object(&self, ray: &Ray) -> (Ray, f32) {
+        let direction = self.inverse.transform_vector3(ray.direction());
+        let origin = self.inverse.transform_point3(ray.origin());
+        (Ray::new(&origin, &direction, ray.time()), direction.length())
+    }
+
+    /// Moves a hit of the object space ray from `to_object` back to world space.
+    fn to_world<'a>(&self, hit: HitRecord<'a>, scale: f32) -> HitRecord<'a> {
+        let point = self.transform.transform_point3(hit.point());
+        let normal = (self.normal_matrix * hit.normal()).normalize();
+        let dpdu = self.transform.transform_vector3(hit.tangent());
+        HitRecord::new(point, normal, hit.uv(), hit.material(), hit.t() / scale, hit.front()).dpdu(dpdu)
+    }
+
+    /// Converts a solid angle density around the object space `direction` to world space.
+    fn world_pdf(&self, pdf: f32, world_direction: Vec3) -> f32 {
+        // the Jacobian of w -> normalize(M⁻¹ w) is |det M⁻¹| / |M⁻¹ w|³
+        let object_direction = self.inverse.transform_vector3(world_direction);
+        pdf * self.inverse.matrix3.determinant().abs() / object_direction.length().powi(3)
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        let (object_ray, scale) = self.to_object(ray);
+        let hit = self.object.hit(&object_ray, &object_accuracy(accuracy, scale))?;
+        Some(self.to_world(hit, scale))
+    }
+}
+
+impl Boundable for Instance {
+    fn bbox(&self, shutter: &Shutter) -> BBox {
+        let BBox { min, max } = self.object.bbox(shutter);
+        (0..8)
+            .map(|corner| Vec3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            ))
+            .map(|corner| self.transform.transform_point3(corner))
+            .fold(BBox::empty(), |bbox, corner| BBox::merge(&bbox, &BBox { min: corner, max: corner }))
+    }
+}
+
+impl SceneObject for Instance {
+    fn is_bounded(&self) -> bool {
+        self.object.is_bounded()
+    }
+
+    fn is_animated(&self) -> bool {
+        !self.keyframes.is_empty()
+    }
+
+    fn animate(&mut self, frame: f32) {
+        if self.is_animated() {
+            self.set_transform(self.transform_at(frame));
+        }
+    }
+
+    fn hit_counted(&self, ray: &Ray, accuracy: &Accuracy) -> (Option<HitRecord<'_>>, usize) {
+        let (object_ray, scale) = self.to_object(ray);
+        let (hit, steps) = self.object.hit_counted(&object_ray, &object_accuracy(accuracy, scale));
+        (hit.map(|hit| self.to_world(hit, scale)), steps)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.object.is_emissive()
+    }
+
+    fn sample_towards(&self, origin: Vec3, time: TimePoint) -> Option<LightSample> {
+        let object_origin = self.inverse.transform_point3(origin);
+        let sample = self.object.sample_towards(object_origin, time)?;
+
+        let offset = self.transform.transform_vector3(sample.direction * sample.distance);
+        let distance = offset.length();
+        if distance == 0. {
+            return None;
+        }
+        let direction = offset / distance;
+
+        Some(LightSample {
+            direction,
+            distance,
+            pdf: self.world_pdf(sample.pdf, direction),
+        })
+    }
+
+    fn transmittance(&self, ray: &Ray, range: &Accuracy) -> f32 {
+        let (object_ray, scale) = self.to_object(ray);
+        self.object.transmittance(&object_ray, &object_accuracy(range, scale))
+    }
+
+    fn pdf_towards(&self, origin: Vec3, direction: Vec3, time: TimePoint) -> f32 {
+        let object_origin = self.inverse.transform_point3(origin);
+        let object_direction = self.inverse.transform_vector3(direction).normalize();
+        let pdf = self.object.pdf_towards(object_origin, object_direction, time);
+        self.world_pdf(pdf, direction)
+    }
+}
+
+/// The search range of a world space ray along its object space copy, `scale` as given by `to_object`.
+fn object_accuracy(accuracy: &Accuracy, scale: f32) -> Accuracy {
+    Accuracy {
+        min: accuracy.min * scale,
+        max: accuracy.max * scale,
+    }
+}
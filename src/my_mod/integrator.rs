@@ -0,0 +1,216 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use glam::Vec3;
+
+use crate::my_mod::bvh::BVHScene;
+use crate::my_mod::environment::Environment;
+use crate::my_mod::hittable::{Accuracy, Hittable};
+use crate::my_mod::light::Light;
+use crate::my_mod::medium::Atmosphere;
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::SceneObject;
+use crate::my_mod::utils::random_on_unit_sphere;
+use crate::my_mod::vec3;
+
+/// Turns a camera ray into the value of its pixel sample, light or some property of the scene.
+pub trait Integrator: Send + Sync + Debug {
+    fn radiance(&self, scene: &SceneView, ray: Ray) -> Sample;
+}
+
+/// The value of a camera ray.
+#[derive(Debug, Copy, Clone)]
+pub struct Sample {
+    pub radiance: Vec3,
+    /// Whether the ray found something in the scene rather than leaving it.
+    pub hit: bool,
+}
+
+impl Sample {
+    fn hit(radiance: Vec3) -> Sample {
+        Sample { radiance, hit: true }
+    }
+
+    fn miss() -> Sample {
+        Sample { radiance: vec3::zero(), hit: false }
+    }
+}
+
+/// The scene as prepared for rendering.
+pub struct SceneView<'a> {
+    pub bvh: BVHScene<'a>,
+    pub objects: &'a [Box<dyn SceneObject>],
+    /// Indices of the emissive objects.
+    pub emitters: Vec<usize>,
+    pub lights: &'a [Box<dyn Light>],
+    pub background: Vec3,
+    pub environment: Option<&'a Environment>,
+    pub accuracy: &'a Accuracy,
+    pub atmosphere: Option<&'a Atmosphere>,
+    /// Camera rays leaving the scene see nothing instead of the background, for images with alpha.
+    pub transparent: bool,
+}
+
+impl SceneView<'_> {
+    /// Share of light passing along the shadow ray up to just before `distance`.
+    pub fn visibility(&self, shadow_ray: &Ray, distance: f32) -> f32 {
+        let unoccluded = Accuracy {
+            min: self.accuracy.min,
+            max: distance * (1. - 1e-3),
+        };
+        let mut transmittance = self.bvh.transmittance(shadow_ray, &unoccluded);
+        if let Some(atmosphere) = self.atmosphere {
+            transmittance *= atmosphere.transmittance(distance);
+        }
+        transmittance
+    }
+}
+
+/// What the renderer shows: the lit scene, or one of the views for debugging scenes and the BVH.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum IntegratorKind {
+    /// Path tracing.
+    #[default]
+    Path,
+    /// Share of the surroundings not blocked nearby, white where open.
+    AmbientOcclusion,
+    /// Outward surface normals, mapped from [-1, 1] to [0, 1].
+    Normals,
+    /// Distance from the camera.
+    Depth,
+    /// The color of the surface.
+    Albedo,
+    /// Texture coordinates in red and green.
+    Uv,
+    /// Nodes visited to find the first hit, blue for few through green to red for many.
+    BvhCost,
+    /// A color for every distinct material.
+    MaterialId,
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(IntegratorKind::Path),
+            "ao" => Ok(IntegratorKind::AmbientOcclusion),
+            "normals" => Ok(IntegratorKind::Normals),
+            "depth" => Ok(IntegratorKind::Depth),
+            "albedo" => Ok(IntegratorKind::Albedo),
+            "uv" => Ok(IntegratorKind::Uv),
+            "bvh-cost" => Ok(IntegratorKind::BvhCost),
+            "material-id" => Ok(IntegratorKind::MaterialId),
+            _ => Err(format!(
+                "unknown integrator '{}', expected path, ao, normals, depth, albedo, uv, bvh-cost or material-id", s,
+            )),
+        }
+    }
+}
+
+/// Share of cosine-weighted directions above the first hit that travel `distance` without hitting anything.
+#[derive(Debug)]
+pub struct AmbientOcclusion {
+    distance: f32,
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: f32) -> AmbientOcclusion {
+        assert!(distance > 0., "occlusion distance must be positive");
+        AmbientOcclusion { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, scene: &SceneView, ray: Ray) -> Sample {
+        let hit_record = match scene.bvh.hit(&ray, scene.accuracy) {
+            Some(hit_record) => hit_record,
+            None => return Sample::miss(),
+        };
+        let direction = hit_record.normal() + random_on_unit_sphere();
+        if direction.length() < 1e-6 {
+            return Sample::hit(vec3::zero());
+        }
+        let probe = Ray::new(&hit_record.point(), &direction, ray.time());
+        let nearby = Accuracy { min: scene.accuracy.min, max: self.distance };
+        if scene.bvh.hit(&probe, &nearby).is_some() {
+            Sample::hit(vec3::zero())
+        } else {
+            Sample::hit(Vec3::ONE)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum SurfaceProperty {
+    Normal,
+    Depth,
+    Albedo,
+    Uv,
+}
+
+/// Shows a property of the first surface hit, black where rays leave the scene.
+#[derive(Debug)]
+pub struct SurfaceView(pub SurfaceProperty);
+
+impl Integrator for SurfaceView {
+    fn radiance(&self, scene: &SceneView, ray: Ray) -> Sample {
+        let hit_record = match scene.bvh.hit(&ray, scene.accuracy) {
+            Some(hit_record) => hit_record,
+            None => return Sample::miss(),
+        };
+        Sample::hit(match self.0 {
+            SurfaceProperty::Normal => {
+                let normal = if hit_record.front() { hit_record.normal() } else { -hit_record.normal() };
+                0.5 * (normal + Vec3::ONE)
+            }
+            SurfaceProperty::Depth => Vec3::splat(hit_record.t()),
+            SurfaceProperty::Albedo => hit_record.material().albedo(&hit_record),
+            SurfaceProperty::Uv => hit_record.uv().extend(0.),
+        })
+    }
+}
+
+/// Heat map of the bounding boxes and objects visited to find the first hit, on a logarithmic scale
+/// reaching red at 255 steps.
+#[derive(Debug)]
+pub struct BvhCost;
+
+impl Integrator for BvhCost {
+    fn radiance(&self, scene: &SceneView, ray: Ray) -> Sample {
+        let (hit, steps) = scene.bvh.hit_counted(&ray, scene.accuracy);
+        Sample { radiance: heat((steps as f32 + 1.).log2() / 8.), hit: hit.is_some() }
+    }
+}
+
+/// Blue, cyan, green, yellow and red from 0 to 1.
+fn heat(t: f32) -> Vec3 {
+    const COLORS: [[f32; 3]; 5] = [[0., 0., 1.], [0., 1., 1.], [0., 1., 0.], [1., 1., 0.], [1., 0., 0.]];
+    let position = t.clamp(0., 1.) * (COLORS.len() - 1) as f32;
+    let index = (position as usize).min(COLORS.len() - 2);
+    Vec3::from(COLORS[index]).lerp(Vec3::from(COLORS[index + 1]), position - index as f32)
+}
+
+/// Colors every material by the id of its name, materials without one are grey.
+#[derive(Debug)]
+pub struct MaterialId;
+
+impl Integrator for MaterialId {
+    fn radiance(&self, scene: &SceneView, ray: Ray) -> Sample {
+        let hit_record = match scene.bvh.hit(&ray, scene.accuracy) {
+            Some(hit_record) => hit_record,
+            None => return Sample::miss(),
+        };
+        Sample::hit(hit_record.material().id().map_or(Vec3::splat(0.5), id_color))
+    }
+}
+
+/// A color spread from the bits of `id`.
+fn id_color(id: u64) -> Vec3 {
+    // the ids of similar names differ in few bits, mix them all into the channels
+    let hash = (id ^ (id >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    let hash = hash ^ (hash >> 33);
+    // bright enough to tell apart from the black background
+    let channel = |shift: u32| 0.15 + 0.85 * ((hash >> shift) & 0xff) as f32 / 255.;
+    Vec3::new(channel(0), channel(8), channel(16))
+}
@@ -0,0 +1,192 @@
+use std::fmt::Debug;
+
+use glam::Vec3;
+
+use crate::my_mod::angle::Angle;
+use crate::my_mod::ies::IesProfile;
+use crate::my_mod::vec3;
+
+/// Light arriving at a point from a `Light`.
+#[derive(Debug, Copy, Clone)]
+pub struct Illumination {
+    /// Unit vector from the point to the light.
+    pub direction: Vec3,
+    pub distance: f32,
+    /// Irradiance on a surface facing the light.
+    pub irradiance: Vec3,
+}
+
+/// A light source without a surface, like an infinitely small bulb or the far away sun.
+///
+/// Rays can't hit it, so it's only found by the shadow rays of direct lighting and can't be seen
+/// directly or in mirrors.
+pub trait Light: Send + Sync + Debug {
+    /// Light reaching `point` if nothing is in the way.
+    fn illuminate(&self, point: Vec3) -> Option<Illumination>;
+}
+
+/// Shines in all directions from a point, shaped by an optional profile.
+#[derive(Debug)]
+pub struct PointLight {
+    position: Vec3,
+    /// Radiant intensity, per unit of solid angle.
+    intensity: Vec3,
+    profile: Option<Profile>,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Vec3) -> PointLight {
+        assert!(intensity.min_element() >= 0., "light intensity must not be negative");
+        PointLight { position, intensity, profile: None }
+    }
+
+    /// Scales the intensity by an IES profile hanging straight down, its 0° plane facing +x.
+    pub fn profile(mut self, profile: IesProfile) -> Self {
+        self.profile = Some(Profile::new(profile, Vec3::new(0., -1., 0.)));
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn illuminate(&self, point: Vec3) -> Option<Illumination> {
+        let outgoing = point - self.position;
+        let distance = outgoing.length();
+        if distance == 0. {
+            return None;
+        }
+        let outgoing = outgoing / distance;
+        let scale = self.profile.as_ref().map_or(1., |profile| profile.value(outgoing));
+        Some(Illumination {
+            direction: -outgoing,
+            distance,
+            irradiance: scale * self.intensity / (distance * distance),
+        })
+    }
+}
+
+/// A point light limited to a cone, fading out towards its edge.
+#[derive(Debug)]
+pub struct SpotLight {
+    position: Vec3,
+    /// Unit vector along the axis of the cone.
+    direction: Vec3,
+    /// Radiant intensity along the axis.
+    intensity: Vec3,
+    /// Angle from the axis where the light is gone.
+    cone: f32,
+    /// Cosines of the angles from the axis where the light starts fading and where it is gone.
+    cos_falloff: f32,
+    cos_cone: f32,
+    profile: Option<Profile>,
+}
+
+impl SpotLight {
+    /// `cone` is the angle from the axis to the edge of the lit area, within (0, 180) degrees.
+    pub fn new(position: Vec3, direction: Vec3, intensity: Vec3, cone: Angle) -> SpotLight {
+        assert!(direction.length() > 0., "spot light direction must not be zero");
+        assert!(intensity.min_element() >= 0., "light intensity must not be negative");
+        let cone = cone.radians();
+        assert!(0. < cone && cone < std::f32::consts::PI, "spot light cone must be within (0, 180) degrees");
+        SpotLight {
+            position,
+            direction: direction.normalize(),
+            intensity,
+            cone,
+            cos_falloff: cone.cos(),
+            cos_cone: cone.cos(),
+            profile: None,
+        }
+    }
+
+    /// Softens the edge of the cone: the light fades out smoothly over this angle inside it.
+    pub fn falloff(mut self, falloff: Angle) -> Self {
+        let falloff = falloff.radians();
+        assert!((0. ..=self.cone).contains(&falloff), "spot light falloff must be within the cone");
+        self.cos_falloff = (self.cone - falloff).cos();
+        self
+    }
+
+    /// Scales the intensity by an IES profile whose nadir points along the axis of the cone.
+    pub fn profile(mut self, profile: IesProfile) -> Self {
+        self.profile = Some(Profile::new(profile, self.direction));
+        self
+    }
+}
+
+impl Light for SpotLight {
+    fn illuminate(&self, point: Vec3) -> Option<Illumination> {
+        let outgoing = point - self.position;
+        let distance = outgoing.length();
+        if distance == 0. {
+            return None;
+        }
+        let outgoing = outgoing / distance;
+        let cos = Vec3::dot(outgoing, self.direction);
+        if cos <= self.cos_cone {
+            return None;
+        }
+        let edge = if cos >= self.cos_falloff {
+            1.
+        } else {
+            let t = (cos - self.cos_cone) / (self.cos_falloff - self.cos_cone);
+            t * t * (3. - 2. * t)
+        };
+        let scale = edge * self.profile.as_ref().map_or(1., |profile| profile.value(outgoing));
+        Some(Illumination {
+            direction: -outgoing,
+            distance,
+            irradiance: scale * self.intensity / (distance * distance),
+        })
+    }
+}
+
+/// Parallel light from infinitely far away, like sunlight.
+#[derive(Debug)]
+pub struct DirectionalLight {
+    /// Unit vector the light travels along.
+    direction: Vec3,
+    irradiance: Vec3,
+}
+
+impl DirectionalLight {
+    /// `irradiance` falls on surfaces facing against `direction`.
+    pub fn new(direction: Vec3, irradiance: Vec3) -> DirectionalLight {
+        assert!(direction.length() > 0., "directional light direction must not be zero");
+        assert!(irradiance.min_element() >= 0., "light irradiance must not be negative");
+        DirectionalLight { direction: direction.normalize(), irradiance }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn illuminate(&self, _point: Vec3) -> Option<Illumination> {
+        Some(Illumination {
+            direction: -self.direction,
+            distance: f32::INFINITY,
+            irradiance: self.irradiance,
+        })
+    }
+}
+
+/// An IES profile placed with its nadir along `axis`.
+#[derive(Debug)]
+struct Profile {
+    profile: IesProfile,
+    axis: Vec3,
+    /// Where the horizontal angles start, and the direction they grow towards.
+    tangent: Vec3,
+    bitangent: Vec3,
+}
+
+impl Profile {
+    fn new(profile: IesProfile, axis: Vec3) -> Profile {
+        let (tangent, bitangent) = vec3::orthonormal_basis(axis);
+        Profile { profile, axis, tangent, bitangent }
+    }
+
+    /// Relative intensity along the unit vector `outgoing`.
+    fn value(&self, outgoing: Vec3) -> f32 {
+        let vertical = Vec3::dot(outgoing, self.axis).clamp(-1., 1.).acos();
+        let horizontal = f32::atan2(Vec3::dot(outgoing, self.bitangent), Vec3::dot(outgoing, self.tangent));
+        self.profile.value(vertical, horizontal)
+    }
+}
@@ -1,13 +1,19 @@
+use std::f32::consts::PI;
 use std::fmt::Debug;
+use std::str::FromStr;
 
-use glam::Vec3;
-use rand::random;
+use glam::{Vec2, Vec3};
 
+use crate::my_mod::emission::Emission;
 use crate::my_mod::hittable::HitRecord;
 use crate::my_mod::intensity::Intensity;
-use crate::my_mod::material::Material::{Dielectric, Lambertian, Light, Metal};
+use crate::my_mod::material::Material::{Black, Conductor, Dielectric, Emissive, Lambertian, Metal, Named, Volume};
+use crate::my_mod::medium::PhaseFunction;
+use crate::my_mod::microfacet::{conductor_fresnel, Frame, Ggx};
+use crate::my_mod::principled::Principled;
 use crate::my_mod::ray::Ray;
-use crate::my_mod::utils::random_on_unit_sphere;
+use crate::my_mod::texture::Texture;
+use crate::my_mod::utils::{random, random_on_unit_sphere};
 use crate::my_mod::vec3;
 
 #[derive(Debug, Copy, Clone)]
@@ -15,9 +21,9 @@ pub struct Attenuation(f32, f32, f32);
 
 impl Attenuation {
     pub fn new(r: f32, g: f32, b: f32) -> Self {
-        assert!(0. <= r && r <= 1.);
-        assert!(0. <= g && g <= 1.);
-        assert!(0. <= b && b <= 1.);
+        assert!((0. ..=1.).contains(&r));
+        assert!((0. ..=1.).contains(&g));
+        assert!((0. ..=1.).contains(&b));
         Self(r, g, b)
     }
 
@@ -38,49 +44,280 @@ impl Attenuation {
     }
 }
 
+/// How the share of reflected light at a dielectric interface is computed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Fresnel {
+    /// Schlick's polynomial approximation.
+    Schlick,
+    /// The Fresnel equations for unpolarized light.
+    #[default]
+    Exact,
+}
+
+impl Fresnel {
+    /// Reflectance for light arriving at `cos` to the normal, `ratio` is the incident over the transmitted index.
+    pub fn reflectance(&self, cos: f32, ratio: f32) -> f32 {
+        let sin_squared = ratio * ratio * (1. - cos * cos);
+        if sin_squared >= 1. {
+            return 1.;
+        }
+        let transmitted_cos = (1. - sin_squared).sqrt();
+        match self {
+            Fresnel::Schlick => {
+                let r0 = ((1. - ratio) / (1. + ratio)).powi(2);
+                // the angle on the optically thinner side, so total internal reflection is approached continuously
+                let cos = if ratio > 1. { transmitted_cos } else { cos };
+                r0 + (1. - r0) * (1. - cos).powi(5)
+            }
+            Fresnel::Exact => {
+                let perpendicular = (ratio * cos - transmitted_cos) / (ratio * cos + transmitted_cos);
+                let parallel = (cos - ratio * transmitted_cos) / (cos + ratio * transmitted_cos);
+                (perpendicular * perpendicular + parallel * parallel) / 2.
+            }
+        }
+    }
+}
+
+impl FromStr for Fresnel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "schlick" => Ok(Fresnel::Schlick),
+            "exact" => Ok(Fresnel::Exact),
+            _ => Err(format!("unknown Fresnel model '{}', expected schlick or exact", s)),
+        }
+    }
+}
+
 pub enum ScatteringResult {
     None,
-    Light(Intensity),
-    ScatterredRay(Attenuation, Ray),
+    /// The ray continues with its intensity scaled by the weight, which may exceed one per channel
+    /// when a BSDF is sampled only roughly in proportion to its value.
+    ScatterredRay(Vec3, Ray),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Material {
     Lambertian {
-        albedo: Attenuation,
+        albedo: Texture,
     },
     Dielectric {
         refraction_index: f32,
+        fresnel: Fresnel,
+        /// Beer–Lambert absorption coefficients per unit of distance travelled inside.
+        absorption: Vec3,
+        /// An infinitely thin shell, light passes it without bending and reflects off both sides.
+        thin: bool,
     },
     Metal {
-        albedo: Attenuation,
+        albedo: Texture,
         fuzz: f32,
     },
-    Light {
-        emit: Intensity,
-    }
+    /// A metal with the complex refractive index `eta + i k` and GGX microfacets.
+    Conductor {
+        eta: Vec3,
+        k: Vec3,
+        microfacet: Ggx,
+    },
+    /// Glass with GGX microfacets, reflecting and refracting by the exact Fresnel equations.
+    RoughDielectric {
+        refraction_index: f32,
+        /// Beer–Lambert absorption coefficients per unit of distance travelled inside.
+        absorption: Vec3,
+        microfacet: Ggx,
+    },
+    Principled(Box<Principled>),
+    /// Absorbs all light, the base of pure light sources.
+    Black,
+    /// Any other material that also gives off light.
+    Emissive {
+        material: Box<Material>,
+        emission: Emission,
+    },
+    /// The inside of a participating medium, scatters by its phase function.
+    Volume {
+        albedo: Texture,
+        phase: PhaseFunction,
+    },
+    /// A material loaded under a name, which the material ID view tells by `id`.
+    Named {
+        material: Box<Material>,
+        id: u64,
+    },
 }
 
 impl Material {
-    pub fn lambertian(albedo: Attenuation) -> Material {
-        Lambertian { albedo }
+    pub fn lambertian(albedo: impl Into<Texture>) -> Material {
+        Lambertian { albedo: albedo.into() }
     }
 
     pub fn dielectric(refraction_index: f32) -> Material {
-        Dielectric { refraction_index }
+        Dielectric {
+            refraction_index,
+            fresnel: Fresnel::default(),
+            absorption: Vec3::ZERO,
+            thin: false,
+        }
+    }
+
+    pub fn metal(albedo: impl Into<Texture>, fuzz: f32) -> Material {
+        Metal { albedo: albedo.into(), fuzz }
+    }
+
+    /// `roughness` along the tangent and the bitangent of the surface, see `Ggx::new`.
+    pub fn conductor(eta: Vec3, k: Vec3, roughness: Vec2) -> Material {
+        Conductor { eta, k, microfacet: Ggx::new(roughness) }
     }
 
-    pub fn metal(albedo: Attenuation, fuzz: f32) -> Material {
-        Metal { albedo, fuzz }
+    pub fn principled(principled: Principled) -> Material {
+        Material::Principled(Box::new(principled))
     }
 
+    /// A two-sided light source that doesn't reflect.
     pub fn light(emit: Intensity) -> Material {
-        Light { emit }
+        let strength = emit.r().max(emit.g()).max(emit.b());
+        let color = if strength > 0. {
+            Attenuation::new(emit.r() / strength, emit.g() / strength, emit.b() / strength)
+        } else {
+            Attenuation::new(0., 0., 0.)
+        };
+        Material::emissive(Black, Emission::new(color, strength).two_sided(true))
+    }
+
+    pub fn emissive(material: Material, emission: Emission) -> Material {
+        Emissive { material: Box::new(material), emission }
+    }
+
+    pub fn volume(albedo: impl Into<Texture>, phase: PhaseFunction) -> Material {
+        Volume { albedo: albedo.into(), phase }
+    }
+
+    /// Tags the material with an id of `name`, the same for the same name in every run.
+    pub fn named(self, name: &str) -> Material {
+        // FNV-1a, unlike the standard hasher its output is fixed
+        let id = name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        Named { material: Box::new(self), id }
+    }
+
+    /// The id given by `named`, if any.
+    pub(crate) fn id(&self) -> Option<u64> {
+        match self {
+            Named { id, .. } => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Resolves an emission given as a power for a shape with the given surface area.
+    pub fn spread_over(self, area: f32) -> Material {
+        match self {
+            Emissive { material, emission } => Emissive { material, emission: emission.spread_over(area) },
+            Named { material, id } => Named { material: Box::new(material.spread_over(area)), id },
+            material => material,
+        }
+    }
+
+    /// Whether the emission is given as a power, which needs `spread_over` before rendering.
+    pub fn emits_power(&self) -> bool {
+        match self {
+            Emissive { emission, .. } => emission.is_power(),
+            Named { material, .. } => material.emits_power(),
+            _ => false,
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        match self {
+            Emissive { material, emission } => !emission.is_black() || material.is_emissive(),
+            Named { material, .. } => material.is_emissive(),
+            _ => false,
+        }
+    }
+
+    /// Radiance emitted at the hit point back along the ray.
+    pub fn emitted(&self, hit_record: &HitRecord) -> Vec3 {
+        match self {
+            Emissive { material, emission } => emission.radiance(hit_record) + material.emitted(hit_record),
+            Named { material, .. } => material.emitted(hit_record),
+            _ => vec3::zero(),
+        }
+    }
+
+    /// Whether the material scatters into a discrete set of directions, or not at all,
+    /// which `evaluate` can't describe.
+    pub(crate) fn is_discrete(&self) -> bool {
+        match self {
+            Lambertian { .. } | Volume { .. } | Material::Principled(_) => false,
+            Conductor { microfacet, .. } | Material::RoughDielectric { microfacet, .. } => microfacet.is_smooth(),
+            Emissive { material, .. } | Named { material, .. } => material.is_discrete(),
+            Dielectric { .. } | Metal { .. } | Black => true,
+        }
+    }
+
+    /// Whether hits are scattering events inside a medium rather than on a surface.
+    pub(crate) fn is_volume(&self) -> bool {
+        match self {
+            Volume { .. } => true,
+            Emissive { material, .. } | Named { material, .. } => material.is_volume(),
+            _ => false,
+        }
+    }
+
+    /// The color of the surface at the hit point, as seen head-on: the share of light it reflects
+    /// or transmits without regard to direction.
+    pub(crate) fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        match self {
+            Lambertian { albedo } | Metal { albedo, .. } | Volume { albedo, .. } =>
+                albedo.value(hit_record.uv(), hit_record.point()).into(),
+            Conductor { eta, k, .. } => conductor_fresnel(1., *eta, *k),
+            Dielectric { .. } | Material::RoughDielectric { .. } => Vec3::ONE,
+            Material::Principled(principled) => principled.albedo(hit_record),
+            Emissive { material, .. } | Named { material, .. } => material.albedo(hit_record),
+            Black => vec3::zero(),
+        }
+    }
+
+    /// BSDF times cosine from travelling along `incoming` into `direction`, and the density with which
+    /// `scatter` picks it. `None` for materials scattering into a discrete set of directions, which
+    /// can't be light sampled.
+    pub(crate) fn evaluate(&self, incoming: Vec3, hit_record: &HitRecord, direction: Vec3) -> Option<(Vec3, f32)> {
+        match *self {
+            Material::Lambertian { ref albedo } => {
+                let cos = Vec3::dot(hit_record.normal(), direction).max(0.);
+                let albedo: Vec3 = albedo.value(hit_record.uv(), hit_record.point()).into();
+                Some((albedo * cos / PI, cos / PI))
+            }
+            Material::Volume { ref albedo, phase } => {
+                let pdf = phase.pdf(incoming, direction);
+                let albedo: Vec3 = albedo.value(hit_record.uv(), hit_record.point()).into();
+                Some((albedo * pdf, pdf))
+            }
+            Material::Conductor { eta, k, microfacet } if !microfacet.is_smooth() => {
+                let frame = Frame::new(hit_record.normal(), hit_record.tangent());
+                let (wo, wi) = (frame.to_local(-incoming), frame.to_local(direction));
+                let (value, pdf) = microfacet.reflection(wo, wi);
+                let fresnel = conductor_fresnel(Vec3::dot(wo, (wo + wi).normalize()), eta, k);
+                Some((fresnel * value, pdf))
+            }
+            Material::RoughDielectric { refraction_index, absorption, microfacet } if !microfacet.is_smooth() => {
+                let frame = Frame::new(hit_record.normal(), hit_record.tangent());
+                let (wo, wi) = (frame.to_local(-incoming), frame.to_local(direction));
+                let ratio = if hit_record.front() { 1. / refraction_index } else { refraction_index };
+                let (value, pdf) = microfacet.dielectric(wo, wi, ratio);
+                Some((value * absorbed(hit_record, absorption), pdf))
+            }
+            Material::Principled(ref principled) => Some(principled.evaluate(incoming, hit_record, direction)),
+            Material::Emissive { ref material, .. } | Material::Named { ref material, .. } =>
+                material.evaluate(incoming, hit_record, direction),
+            _ => None,
+        }
     }
 
     pub(crate) fn scatter(&self, input_ray: &Ray, hit_record: &HitRecord) -> ScatteringResult {
         match *self {
-            Material::Lambertian { albedo } => {
+            Material::Lambertian { ref albedo } => {
                 let scatter_direction = {
                     let direction = hit_record.normal() + random_on_unit_sphere();
                     let eps = 1e-5;
@@ -91,42 +328,100 @@ impl Material {
                     }
                 };
 
-                let scattered = Ray::new(&hit_record.point(), &scatter_direction);
-                ScatteringResult::ScatterredRay(albedo, scattered)
+                let scattered = Ray::new(&hit_record.point(), &scatter_direction, input_ray.time());
+                let albedo = albedo.value(hit_record.uv(), hit_record.point());
+                ScatteringResult::ScatterredRay(albedo.into(), scattered)
             }
-            Material::Dielectric { refraction_index } => {
-                let refraction_index = refraction_index;
-                let attenuation = Attenuation::new(1., 1., 1.);
-                let angle_ratio = if hit_record.front() {
-                    1. / refraction_index
+            Material::Dielectric { refraction_index, fresnel, absorption, thin } => {
+                let dir = input_ray.direction();
+                let n = hit_record.normal();
+                let cos = Vec3::dot(-dir, n).clamp(0., 1.);
+                let reflected = vec3::reflect(dir, n);
+
+                let direction = if thin {
+                    // the light bouncing between both sides sums to 2R / (1 + R)
+                    let reflectance = fresnel.reflectance(cos, 1. / refraction_index);
+                    if random::<f32>() < 2. * reflectance / (1. + reflectance) { reflected } else { dir }
                 } else {
-                    refraction_index
-                };
-                let scattered = Ray::new(&hit_record.point(), &{
-                    let dir = input_ray.direction();
-                    let n = hit_record.normal();
+                    let angle_ratio = if hit_record.front() {
+                        1. / refraction_index
+                    } else {
+                        refraction_index
+                    };
                     match vec3::refract(dir, n, angle_ratio) {
-                        Some(direction) => direction,
-                        None => vec3::reflect(dir, n),
+                        Some(refracted) if random::<f32>() >= fresnel.reflectance(cos, angle_ratio) => refracted,
+                        _ => reflected,
                     }
-                });
+                };
+
+                let attenuation = if thin { Vec3::ONE } else { absorbed(hit_record, absorption) };
+                let scattered = Ray::new(&hit_record.point(), &direction, input_ray.time());
                 ScatteringResult::ScatterredRay(attenuation, scattered)
             }
-            Material::Metal { albedo, fuzz } => {
+            Material::Metal { ref albedo, fuzz } => {
                 let reflected = vec3::reflect(input_ray.direction(), hit_record.normal());
                 let scattered = Ray::new(
                     &hit_record.point(),
                     &(reflected + fuzz * random_on_unit_sphere()),
+                    input_ray.time(),
                 );
                 if Vec3::dot(reflected, hit_record.normal()) > 0. {
-                    ScatteringResult::ScatterredRay(albedo, scattered)
+                    let albedo = albedo.value(hit_record.uv(), hit_record.point());
+                    ScatteringResult::ScatterredRay(albedo.into(), scattered)
                 } else {
                     ScatteringResult::None
                 }
             }
-            Material::Light { emit } => {
-                ScatteringResult::Light(emit)
+            Material::Conductor { eta, k, microfacet } => {
+                let frame = Frame::new(hit_record.normal(), hit_record.tangent());
+                let wo = frame.to_local(-input_ray.direction());
+                let wi = match microfacet.sample_reflection(wo) {
+                    Some(wi) if wo.z > 0. => wi,
+                    _ => return ScatteringResult::None,
+                };
+
+                let fresnel = conductor_fresnel(Vec3::dot(wo, (wo + wi).normalize()), eta, k);
+                let weight = fresnel * microfacet.g2(wo, wi) / microfacet.g1(wo);
+                let scattered = Ray::new(&hit_record.point(), &frame.to_world(wi), input_ray.time());
+                ScatteringResult::ScatterredRay(weight, scattered)
+            }
+            Material::RoughDielectric { refraction_index, absorption, microfacet } => {
+                let frame = Frame::new(hit_record.normal(), hit_record.tangent());
+                let wo = frame.to_local(-input_ray.direction());
+                let ratio = if hit_record.front() { 1. / refraction_index } else { refraction_index };
+                let wi = match microfacet.sample_dielectric(wo, ratio) {
+                    Some(wi) if wo.z > 0. => wi,
+                    _ => return ScatteringResult::None,
+                };
+
+                let weight = microfacet.g2(wo, wi) / microfacet.g1(wo) * absorbed(hit_record, absorption);
+                let scattered = Ray::new(&hit_record.point(), &frame.to_world(wi), input_ray.time());
+                ScatteringResult::ScatterredRay(weight, scattered)
+            }
+            Material::Principled(ref principled) => match principled.sample(input_ray.direction(), hit_record) {
+                Some((weight, direction)) => {
+                    let scattered = Ray::new(&hit_record.point(), &direction, input_ray.time());
+                    ScatteringResult::ScatterredRay(weight, scattered)
+                }
+                None => ScatteringResult::None,
+            },
+            Material::Black => ScatteringResult::None,
+            Material::Emissive { ref material, .. } | Material::Named { ref material, .. } =>
+                material.scatter(input_ray, hit_record),
+            Material::Volume { ref albedo, phase } => {
+                let scattered = Ray::new(&hit_record.point(), &phase.sample(input_ray.direction()), input_ray.time());
+                let albedo = albedo.value(hit_record.uv(), hit_record.point());
+                ScatteringResult::ScatterredRay(albedo.into(), scattered)
             }
         }
     }
 }
+
+/// Beer–Lambert transmittance of the path to a back face hit, which travelled inside since its origin.
+fn absorbed(hit_record: &HitRecord, absorption: Vec3) -> Vec3 {
+    if hit_record.front() {
+        Vec3::ONE
+    } else {
+        (-absorption * hit_record.t()).exp()
+    }
+}
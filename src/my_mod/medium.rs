@@ -0,0 +1,230 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3};
+
+use crate::my_mod::bbox::BBox;
+use crate::my_mod::bvh::Boundable;
+use crate::my_mod::grid::DensityGrid;
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::material::Material;
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::SceneObject;
+use crate::my_mod::texture::Texture;
+use crate::my_mod::time::Shutter;
+use crate::my_mod::utils::random;
+use crate::my_mod::vec3;
+
+/// Distribution of the directions light continues in after scattering in a medium.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PhaseFunction {
+    Isotropic,
+    /// Forward scattering for positive `g`, backward for negative, `g` is within (-1, 1).
+    HenyeyGreenstein { g: f32 },
+}
+
+impl PhaseFunction {
+    pub fn henyey_greenstein(g: f32) -> PhaseFunction {
+        assert!(-1. < g && g < 1., "asymmetry must be within (-1, 1)");
+        PhaseFunction::HenyeyGreenstein { g }
+    }
+
+    /// Density of scattering from travelling along `incoming` into `outgoing`, both unit vectors.
+    pub fn pdf(&self, incoming: Vec3, outgoing: Vec3) -> f32 {
+        match *self {
+            PhaseFunction::Isotropic => 1. / (4. * PI),
+            PhaseFunction::HenyeyGreenstein { g } => {
+                let cos = Vec3::dot(incoming, outgoing);
+                let denominator = 1. + g * g - 2. * g * cos;
+                (1. - g * g) / (4. * PI * denominator * denominator.sqrt())
+            }
+        }
+    }
+
+    /// Picks an outgoing direction with density `pdf`.
+    pub fn sample(&self, incoming: Vec3) -> Vec3 {
+        let xi = random::<f32>();
+        let cos = match *self {
+            PhaseFunction::HenyeyGreenstein { g } if g.abs() > 1e-3 => {
+                let s = (1. - g * g) / (1. - g + 2. * g * xi);
+                ((1. + g * g - s * s) / (2. * g)).clamp(-1., 1.)
+            }
+            _ => 1. - 2. * xi,
+        };
+        let sin = (1. - cos * cos).max(0.).sqrt();
+        let phi = 2. * PI * random::<f32>();
+        let (tangent, bitangent) = vec3::orthonormal_basis(incoming);
+        cos * incoming + sin * (phi.cos() * tangent + phi.sin() * bitangent)
+    }
+}
+
+/// Distance to the next collision in a medium of the given density.
+fn sample_free_flight(density: f32) -> f32 {
+    -(1. - random::<f32>()).ln() / density
+}
+
+/// A scattering event inside a medium, its normal points back along the ray, see `Material::Volume`.
+fn scattering_record<'a>(ray: &Ray, t: f32, material: &'a Material) -> HitRecord<'a> {
+    HitRecord::new(ray.at(t), -ray.direction(), Vec2::ZERO, material, t, true)
+}
+
+/// Fog or smoke of uniform density filling a closed boundary.
+///
+/// A ray entering the boundary scatters after an exponentially distributed distance, or passes
+/// through when that is beyond the exit. The boundary's normals must face outwards, its material
+/// isn't used.
+#[derive(Debug)]
+pub struct ConstantMedium {
+    boundary: Box<dyn SceneObject>,
+    density: f32,
+    material: Material,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Box<dyn SceneObject>,
+        density: f32,
+        albedo: impl Into<Texture>,
+        phase: PhaseFunction,
+    ) -> ConstantMedium {
+        assert!(density > 0., "medium density must be positive");
+        ConstantMedium {
+            boundary,
+            density,
+            material: Material::volume(albedo, phase),
+        }
+    }
+
+    /// The part of `accuracy` the ray spends inside the boundary.
+    fn inside(&self, ray: &Ray, accuracy: &Accuracy) -> Option<(f32, f32)> {
+        let first = self.boundary.hit(ray, accuracy)?;
+        // entering through the first boundary hit means the ray starts outside
+        if first.front() {
+            let inside = Accuracy { min: first.t() + 1e-4, max: accuracy.max };
+            Some((first.t(), self.boundary.hit(ray, &inside).map_or(accuracy.max, |hit| hit.t())))
+        } else {
+            Some((accuracy.min, first.t()))
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        let (enter, exit) = self.inside(ray, accuracy)?;
+        let t = enter + sample_free_flight(self.density);
+        if t < exit {
+            Some(scattering_record(ray, t, &self.material))
+        } else {
+            None
+        }
+    }
+}
+
+impl Boundable for ConstantMedium {
+    fn bbox(&self, shutter: &Shutter) -> BBox {
+        self.boundary.bbox(shutter)
+    }
+}
+
+impl SceneObject for ConstantMedium {
+    fn is_bounded(&self) -> bool {
+        self.boundary.is_bounded()
+    }
+
+    fn transmittance(&self, ray: &Ray, range: &Accuracy) -> f32 {
+        match self.inside(ray, range) {
+            Some((enter, exit)) => (-self.density * (exit - enter)).exp(),
+            None => 1.,
+        }
+    }
+}
+
+/// Smoke with a density varying over a grid, scaled by `density`.
+///
+/// The density is bounded by the grid maximum, so collisions are found by delta tracking:
+/// tentative collisions are sampled as in a constant medium of the maximum density and accepted
+/// with the ratio of the actual density. Shadow rays use ratio tracking to estimate the transmittance.
+#[derive(Debug)]
+pub struct GridMedium {
+    grid: DensityGrid,
+    density: f32,
+    majorant: f32,
+    material: Material,
+}
+
+impl GridMedium {
+    pub fn new(grid: DensityGrid, density: f32, albedo: impl Into<Texture>, phase: PhaseFunction) -> GridMedium {
+        assert!(density > 0., "medium density must be positive");
+        GridMedium {
+            majorant: grid.max() * density,
+            grid,
+            density,
+            material: Material::volume(albedo, phase),
+        }
+    }
+
+    /// Tentative collisions along the ray within `range`, with the share of the majorant that is real.
+    fn collisions<'a>(&'a self, ray: &'a Ray, range: &Accuracy) -> impl Iterator<Item = (f32, f32)> + 'a {
+        let (enter, exit) = match self.grid.bbox().intersect(ray, range) {
+            Some(inside) if self.majorant > 0. => inside,
+            _ => (0., 0.),
+        };
+        let mut t = enter;
+        std::iter::from_fn(move || {
+            t += sample_free_flight(self.majorant);
+            (t < exit).then(|| (t, self.density * self.grid.density(ray.at(t)) / self.majorant))
+        })
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        let (t, _) = self.collisions(ray, accuracy).find(|(_, real)| random::<f32>() < *real)?;
+        Some(scattering_record(ray, t, &self.material))
+    }
+}
+
+impl Boundable for GridMedium {
+    fn bbox(&self, _shutter: &Shutter) -> BBox {
+        self.grid.bbox()
+    }
+}
+
+impl SceneObject for GridMedium {
+    fn transmittance(&self, ray: &Ray, range: &Accuracy) -> f32 {
+        self.collisions(ray, range).map(|(_, real)| 1. - real).product()
+    }
+}
+
+/// A homogeneous medium filling the whole scene.
+///
+/// Nothing is infinitely far away in it, so rays leaving the scene scatter before reaching the background.
+#[derive(Debug)]
+pub struct Atmosphere {
+    density: f32,
+    material: Material,
+}
+
+impl Atmosphere {
+    pub fn new(density: f32, albedo: impl Into<Texture>, phase: PhaseFunction) -> Atmosphere {
+        assert!(density > 0., "atmosphere density must be positive");
+        Atmosphere {
+            density,
+            material: Material::volume(albedo, phase),
+        }
+    }
+
+    /// Where the ray scatters, if it does so before reaching `max`.
+    pub(crate) fn hit(&self, ray: &Ray, max: f32) -> Option<HitRecord<'_>> {
+        let t = sample_free_flight(self.density);
+        if t < max {
+            Some(scattering_record(ray, t, &self.material))
+        } else {
+            None
+        }
+    }
+
+    /// Share of light passing unscattered over `distance`.
+    pub(crate) fn transmittance(&self, distance: f32) -> f32 {
+        (-self.density * distance).exp()
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3};
+
+use crate::my_mod::bbox::BBox;
+use crate::my_mod::bvh::Boundable;
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::material::Material;
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::{LightSample, SceneObject};
+use crate::my_mod::time::{Shutter, TimePoint};
+use crate::my_mod::utils::random;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Vertex {
+    pub position: usize,
+    pub normal: Option<usize>,
+    pub uv: Option<usize>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Face {
+    pub vertices: [Vertex; 3],
+    pub material: usize,
+}
+
+/// Vertex buffers shared by all triangles of a model.
+#[derive(Debug, Default)]
+pub struct TriangleMesh {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    faces: Vec<Face>,
+    materials: Vec<Material>,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<Vec2>,
+        faces: Vec<Face>,
+        materials: Vec<Material>,
+    ) -> Self {
+        for face in &faces {
+            assert!(face.material < materials.len());
+            for vertex in &face.vertices {
+                assert!(vertex.position < positions.len());
+                assert!(vertex.normal.is_none_or(|i| i < normals.len()));
+                assert!(vertex.uv.is_none_or(|i| i < uvs.len()));
+            }
+        }
+
+        Self {
+            positions,
+            normals,
+            uvs,
+            faces,
+            materials,
+        }
+    }
+
+    pub fn triangles(self) -> Vec<Triangle> {
+        let mesh = Arc::new(self);
+        (0..mesh.faces.len())
+            .map(|face| Triangle { mesh: mesh.clone(), face })
+            .collect()
+    }
+
+    fn positions(&self, face: usize) -> [Vec3; 3] {
+        let [a, b, c] = self.faces[face].vertices;
+        [
+            self.positions[a.position],
+            self.positions[b.position],
+            self.positions[c.position],
+        ]
+    }
+
+    fn normal(&self, face: usize, u: f32, v: f32) -> Option<Vec3> {
+        let [a, b, c] = self.faces[face].vertices;
+        let normal = (1. - u - v) * self.normals[a.normal?]
+            + u * self.normals[b.normal?]
+            + v * self.normals[c.normal?];
+        Some(normal.normalize())
+    }
+
+    fn uv(&self, face: usize, u: f32, v: f32) -> Option<Vec2> {
+        let [a, b, c] = self.faces[face].vertices;
+        Some((1. - u - v) * self.uvs[a.uv?] + u * self.uvs[b.uv?] + v * self.uvs[c.uv?])
+    }
+
+    /// Change of the position with the texture coordinate `u`, none without usable texture coordinates.
+    fn dpdu(&self, face: usize) -> Option<Vec3> {
+        let [a, b, c] = self.faces[face].vertices;
+        let [p0, p1, p2] = self.positions(face);
+        let uv0 = self.uvs[a.uv?];
+        let duv1 = self.uvs[b.uv?] - uv0;
+        let duv2 = self.uvs[c.uv?] - uv0;
+        let det = duv1.x * duv2.y - duv1.y * duv2.x;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        Some((duv2.y * (p1 - p0) - duv1.y * (p2 - p0)) / det)
+    }
+}
+
+#[derive(Debug)]
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        let [p0, p1, p2] = self.mesh.positions(self.face);
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+
+        let p = Vec3::cross(ray.direction(), e2);
+        let det = Vec3::dot(e1, p);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1. / det;
+
+        let s = ray.origin() - p0;
+        let u = Vec3::dot(s, p) * inv_det;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
+
+        let q = Vec3::cross(s, e1);
+        let v = Vec3::dot(ray.direction(), q) * inv_det;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+
+        let t = Vec3::dot(e2, q) * inv_det;
+        let Accuracy { min, max } = *accuracy;
+        if t < min || max < t {
+            return None;
+        }
+
+        let geometric_normal = Vec3::cross(e1, e2).normalize();
+        let front = Vec3::dot(ray.direction(), geometric_normal) < 0.;
+        let outward_normal = self.mesh
+            .normal(self.face, u, v)
+            .map(|n| if Vec3::dot(n, geometric_normal) < 0. { -n } else { n })
+            .unwrap_or(geometric_normal);
+        let normal = if front {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+
+        let material = self.material();
+
+        // meshes without texture coordinates get the barycentric ones
+        let uv = self.mesh.uv(self.face, u, v).unwrap_or_else(|| Vec2::new(u, v));
+
+        let dpdu = self.mesh.dpdu(self.face).unwrap_or(e1);
+
+        Some(HitRecord::new(ray.at(t), normal, uv, material, t, front).dpdu(dpdu))
+    }
+}
+
+impl Boundable for Triangle {
+    fn bbox(&self, _shutter: &Shutter) -> BBox {
+        let [p0, p1, p2] = self.mesh.positions(self.face);
+        // flat triangles still need some thickness for the slab test
+        let eps = Vec3::new(1e-4, 1e-4, 1e-4);
+        BBox {
+            min: p0.min(p1).min(p2) - eps,
+            max: p0.max(p1).max(p2) + eps,
+        }
+    }
+}
+
+impl Triangle {
+    fn material(&self) -> &Material {
+        &self.mesh.materials[self.mesh.faces[self.face].material]
+    }
+
+    /// Converts the uniform area density to solid angle as seen along `direction`.
+    fn area_pdf(&self, direction: Vec3, distance: f32) -> f32 {
+        let [p0, p1, p2] = self.mesh.positions(self.face);
+        let cross = Vec3::cross(p1 - p0, p2 - p0);
+        let area = cross.length() / 2.;
+        let cos = Vec3::dot(cross.normalize(), direction).abs();
+        if area == 0. || cos < 1e-6 {
+            return 0.;
+        }
+        distance.powi(2) / (cos * area)
+    }
+}
+
+impl SceneObject for Triangle {
+    fn is_emissive(&self) -> bool {
+        self.material().is_emissive()
+    }
+
+    fn sample_towards(&self, origin: Vec3, _time: TimePoint) -> Option<LightSample> {
+        let [p0, p1, p2] = self.mesh.positions(self.face);
+        let su = random::<f32>().sqrt();
+        let v = random::<f32>();
+        let point = (1. - su) * p0 + su * (1. - v) * p1 + su * v * p2;
+
+        let to_point = point - origin;
+        let distance = to_point.length();
+        if distance == 0. {
+            return None;
+        }
+        let direction = to_point / distance;
+        let pdf = self.area_pdf(direction, distance);
+        if pdf == 0. {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            pdf,
+        })
+    }
+
+    fn pdf_towards(&self, origin: Vec3, direction: Vec3, time: TimePoint) -> f32 {
+        let ray = Ray::new(&origin, &direction, time);
+        match self.hit(&ray, &Accuracy { min: 1e-4, max: f32::INFINITY }) {
+            Some(hit) => self.area_pdf(ray.direction(), hit.t()),
+            None => 0.,
+        }
+    }
+}
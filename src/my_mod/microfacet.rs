@@ -0,0 +1,225 @@
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+use glam::{Vec2, Vec3};
+
+use crate::my_mod::material::Fresnel;
+use crate::my_mod::utils::random;
+use crate::my_mod::vec3;
+
+/// Below this width the distribution is treated as a perfectly smooth surface.
+const SMOOTH: f32 = 1e-3;
+/// Narrowest width along either axis, so a smooth axis of a rough surface doesn't divide by zero.
+const MIN_ALPHA: f32 = 1e-4;
+
+/// GGX (Trowbridge–Reitz) distribution of microfacet normals.
+///
+/// Directions are local to the surface, `z` along the normal and `x` along the tangent of `Frame`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ggx {
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl Ggx {
+    /// Perceptual roughness along the tangent and the bitangent, squared to the distribution widths.
+    pub fn new(roughness: Vec2) -> Ggx {
+        assert!((0. ..=1.).contains(&roughness.x) && (0. ..=1.).contains(&roughness.y), "roughness must be within [0, 1]");
+        Ggx {
+            alpha_x: (roughness.x * roughness.x).max(MIN_ALPHA),
+            alpha_y: (roughness.y * roughness.y).max(MIN_ALPHA),
+        }
+    }
+
+    /// Whether the surface is a mirror, which has to be handled without the distribution.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < SMOOTH
+    }
+
+    /// Density of microfacet normals `h` per projected area.
+    pub fn d(&self, h: Vec3) -> f32 {
+        if h.z <= 0. {
+            return 0.;
+        }
+        let e = (h.x / self.alpha_x).powi(2) + (h.y / self.alpha_y).powi(2) + h.z * h.z;
+        1. / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    fn lambda(&self, w: Vec3) -> f32 {
+        if w.z == 0. {
+            return f32::INFINITY;
+        }
+        let tan_squared = ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / (w.z * w.z);
+        ((1. + tan_squared).sqrt() - 1.) / 2.
+    }
+
+    /// Share of microfacets visible from `w`.
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Height-correlated share of microfacets visible from both directions.
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Picks a microfacet normal as seen from `wo`, with density `g1(wo) (wo · h) d(h) / wo.z` (Heitz 2018).
+    pub fn sample_visible(&self, wo: Vec3) -> Vec3 {
+        // in the stretched space the distribution is a hemisphere
+        let v = Vec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).normalize();
+        let length_squared = v.x * v.x + v.y * v.y;
+        let t1 = if length_squared > 0. {
+            Vec3::new(-v.y, v.x, 0.) / length_squared.sqrt()
+        } else {
+            Vec3::X
+        };
+        let t2 = Vec3::cross(v, t1);
+
+        let r = random::<f32>().sqrt();
+        let phi = 2. * PI * random::<f32>();
+        let p1 = r * phi.cos();
+        let s = (1. + v.z) / 2.;
+        let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * r * phi.sin();
+        let n = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * v;
+
+        Vec3::new(self.alpha_x * n.x, self.alpha_y * n.y, n.z.max(0.)).normalize()
+    }
+
+    /// Reflection lobe without Fresnel: the BRDF times `wi.z` and the density of `sample_reflection`.
+    pub fn reflection(&self, wo: Vec3, wi: Vec3) -> (f32, f32) {
+        if wo.z <= 0. || wi.z <= 0. {
+            return (0., 0.);
+        }
+        let h = (wo + wi).normalize();
+        let d = self.d(h);
+        (d * self.g2(wo, wi) / (4. * wo.z), self.g1(wo) * d / (4. * wo.z))
+    }
+
+    /// Mirrors `wo` on a visible microfacet, `None` if that leads below the surface.
+    pub fn sample_reflection(&self, wo: Vec3) -> Option<Vec3> {
+        let wi = vec3::reflect(-wo, self.sample_visible(wo));
+        (wi.z > 0.).then_some(wi)
+    }
+
+    /// Rough dielectric interface, reflecting and refracting by the exact Fresnel equations:
+    /// the BSDF times `|wi.z|` and the density of `sample_dielectric`.
+    /// `ratio` is the index on the side of `wo` over the one on the other side.
+    pub fn dielectric(&self, wo: Vec3, wi: Vec3, ratio: f32) -> (f32, f32) {
+        if wo.z <= 0. || wi.z == 0. {
+            return (0., 0.);
+        }
+        let g1 = self.g1(wo);
+        let g2 = self.g2(wo, wi);
+
+        if wi.z > 0. {
+            let h = (wo + wi).normalize();
+            let d = self.d(h);
+            let fresnel = Fresnel::Exact.reflectance(Vec3::dot(wo, h), ratio);
+            return (fresnel * d * g2 / (4. * wo.z), fresnel * g1 * d / (4. * wo.z));
+        }
+
+        // the microfacet normal refracting wo into wi
+        let eta = 1. / ratio;
+        let h = (wo + eta * wi).normalize();
+        let h = if h.z < 0. { -h } else { h };
+        let (wo_h, wi_h) = (Vec3::dot(wo, h), Vec3::dot(wi, h));
+        if wo_h <= 0. || wi_h >= 0. {
+            return (0., 0.);
+        }
+        let d = self.d(h);
+        let transmitted = 1. - Fresnel::Exact.reflectance(wo_h, ratio);
+        let jacobian = eta * eta * -wi_h / (wo_h + eta * wi_h).powi(2);
+        (transmitted * d * g2 * wo_h / wo.z * jacobian, transmitted * g1 * d * wo_h / wo.z * jacobian)
+    }
+
+    /// Reflects or refracts `wo` on a visible microfacet, picked by its Fresnel reflectance.
+    pub fn sample_dielectric(&self, wo: Vec3, ratio: f32) -> Option<Vec3> {
+        let h = self.sample_visible(wo);
+        let reflectance = Fresnel::Exact.reflectance(Vec3::dot(wo, h), ratio);
+        let wi = match vec3::refract(-wo, h, ratio) {
+            Some(refracted) if random::<f32>() >= reflectance => refracted,
+            _ => vec3::reflect(-wo, h),
+        };
+        // the microfacet may send the ray to the wrong side of the macro surface
+        (wi.z != 0. && (wi.z > 0.) == (Vec3::dot(wi, h) > 0.)).then_some(wi)
+    }
+}
+
+/// Orthonormal frame around a surface normal, converts between world and local directions.
+pub struct Frame {
+    tangent: Vec3,
+    bitangent: Vec3,
+    normal: Vec3,
+}
+
+impl Frame {
+    /// `tangent` must be a unit vector perpendicular to the unit `normal`.
+    pub fn new(normal: Vec3, tangent: Vec3) -> Frame {
+        let bitangent = Vec3::cross(normal, tangent);
+        Frame { tangent, bitangent, normal }
+    }
+
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(v, self.tangent), Vec3::dot(v, self.bitangent), Vec3::dot(v, self.normal))
+    }
+
+    pub fn to_world(&self, v: Vec3) -> Vec3 {
+        v.x * self.tangent + v.y * self.bitangent + v.z * self.normal
+    }
+}
+
+/// Reflectance of a conductor with the complex refractive index `eta + i k`, per color channel.
+pub fn conductor_fresnel(cos: f32, eta: Vec3, k: Vec3) -> Vec3 {
+    let cos = cos.clamp(0., 1.);
+    let cos_squared = cos * cos;
+    let sin_squared = 1. - cos_squared;
+    let (eta_squared, k_squared) = (eta * eta, k * k);
+
+    let t0 = eta_squared - k_squared - Vec3::splat(sin_squared);
+    let a_squared_plus_b_squared = vec3::sqrt((t0 * t0 + 4. * eta_squared * k_squared).max(Vec3::ZERO));
+    let t1 = a_squared_plus_b_squared + Vec3::splat(cos_squared);
+    let a = vec3::sqrt(((a_squared_plus_b_squared + t0) / 2.).max(Vec3::ZERO));
+    let t2 = 2. * cos * a;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos_squared * a_squared_plus_b_squared + Vec3::splat(sin_squared * sin_squared);
+    let t4 = t2 * sin_squared;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+
+    ((perpendicular + parallel) / 2.).clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+/// Measured complex refractive indices of common metals at red, green and blue wavelengths.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Metal {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+impl Metal {
+    /// The real and imaginary parts `(eta, k)` of the refractive index.
+    pub fn refractive_index(&self) -> (Vec3, Vec3) {
+        match self {
+            Metal::Gold => (Vec3::new(0.143, 0.374, 1.442), Vec3::new(3.983, 2.385, 1.603)),
+            Metal::Copper => (Vec3::new(0.200, 0.924, 1.102), Vec3::new(3.912, 2.452, 2.142)),
+            Metal::Aluminium => (Vec3::new(1.657, 0.880, 0.521), Vec3::new(9.224, 6.270, 4.837)),
+            Metal::Silver => (Vec3::new(0.155, 0.117, 0.138), Vec3::new(4.828, 3.122, 2.147)),
+        }
+    }
+}
+
+impl FromStr for Metal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gold" => Ok(Metal::Gold),
+            "copper" => Ok(Metal::Copper),
+            "aluminium" => Ok(Metal::Aluminium),
+            "silver" => Ok(Metal::Silver),
+            _ => Err(format!("unknown metal '{}', expected gold, copper, aluminium or silver", s)),
+        }
+    }
+}
@@ -1,18 +1,43 @@
 pub mod angle;
+pub mod bbox;
+pub mod blas;
+pub mod bvh;
 pub mod camera;
+pub mod cli;
+pub mod cuboid;
+pub mod disk;
+pub mod emission;
+pub mod environment;
+pub mod grid;
 pub mod hittable;
+pub mod ies;
 pub mod image;
+pub mod instance;
+pub mod integrator;
 pub mod intensity;
+pub mod light;
 pub mod material;
-pub mod ppm;
+pub mod medium;
+pub mod mesh;
+pub mod microfacet;
+pub mod moving_sphere;
+pub mod obj;
+pub mod path_tracer;
+pub mod perlin;
+pub mod plane;
+pub mod principled;
+pub mod quad;
 pub mod ray;
+pub mod renderer;
+pub mod resolution;
 pub mod rgb;
+pub mod scene;
+pub mod scene_file;
+pub mod sky;
 pub mod sphere;
+pub mod texture;
+pub mod time;
+pub mod tonemap;
 pub mod utils;
 pub mod vec3;
-pub mod time;
-pub mod renderer;
-pub mod resolution;
-pub mod bvh;
-pub mod bbox;
-pub mod scene;
+pub mod writer;
@@ -0,0 +1,88 @@
+use glam::Vec3;
+
+use crate::my_mod::bbox::BBox;
+use crate::my_mod::bvh::Boundable;
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::material::Material;
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::{LightSample, SceneObject};
+use crate::my_mod::sphere::Sphere;
+use crate::my_mod::time::{Shutter, TimePoint};
+
+/// A sphere whose center moves linearly between keyframes and rests before the first and after the last.
+#[derive(Debug)]
+pub struct MovingSphere {
+    sphere: Sphere,
+    keyframes: Vec<(TimePoint, Vec3)>,
+}
+
+impl MovingSphere {
+    /// Moves from `center0` at `time0` to `center1` at `time1`.
+    pub fn linear(
+        center0: Vec3,
+        time0: TimePoint,
+        center1: Vec3,
+        time1: TimePoint,
+        radius: f32,
+        material: Material,
+    ) -> MovingSphere {
+        MovingSphere::keyframed(vec![(time0, center0), (time1, center1)], radius, material)
+    }
+
+    /// Passes through the centers at the given times, the keyframes must be sorted by time.
+    pub fn keyframed(keyframes: Vec<(TimePoint, Vec3)>, radius: f32, material: Material) -> MovingSphere {
+        assert!(!keyframes.is_empty());
+        assert!(keyframes.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        MovingSphere {
+            sphere: Sphere::new(keyframes[0].1, radius, material),
+            keyframes,
+        }
+    }
+
+    pub fn center(&self, time: TimePoint) -> Vec3 {
+        let next = self.keyframes.partition_point(|(t, _)| *t <= time);
+        if next == 0 {
+            return self.keyframes[0].1;
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].1;
+        }
+
+        let (TimePoint(t0), c0) = self.keyframes[next - 1];
+        let (TimePoint(t1), c1) = self.keyframes[next];
+        c0.lerp(c1, (time.0 - t0) / (t1 - t0))
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        self.sphere.hit_at(self.center(ray.time()), ray, accuracy)
+    }
+}
+
+impl Boundable for MovingSphere {
+    fn bbox(&self, shutter: &Shutter) -> BBox {
+        // the path is piecewise linear, so its ends and the keyframes in between bound it
+        let (open, close) = (shutter.open(), shutter.close());
+        self.keyframes.iter()
+            .filter(|(t, _)| open < *t && *t < close)
+            .map(|(_, center)| *center)
+            .chain([self.center(open), self.center(close)])
+            .map(|center| self.sphere.bbox_at(center))
+            .fold(BBox::empty(), |a, b| BBox::merge(&a, &b))
+    }
+}
+
+impl SceneObject for MovingSphere {
+    fn is_emissive(&self) -> bool {
+        self.sphere.is_emissive()
+    }
+
+    fn sample_towards(&self, origin: Vec3, time: TimePoint) -> Option<LightSample> {
+        self.sphere.sample_towards_at(self.center(time), origin)
+    }
+
+    fn pdf_towards(&self, origin: Vec3, direction: Vec3, time: TimePoint) -> f32 {
+        self.sphere.pdf_towards_at(self.center(time), origin, direction)
+    }
+}
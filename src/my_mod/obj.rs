@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use glam::{Vec2, Vec3};
+
+use crate::my_mod::intensity::Intensity;
+use crate::my_mod::material::{Attenuation, Material};
+use crate::my_mod::mesh::{Face, TriangleMesh, Vertex};
+use crate::my_mod::texture::{ImageTexture, Texture, WrapMode};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            ObjError::Parse { path, line, message } =>
+                write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {}
+
+/// Loads a Wavefront OBJ model together with the MTL libraries it references.
+///
+/// Polygons are triangulated as fans. Faces without `usemtl` get a grey `Lambertian`.
+pub fn load(path: impl AsRef<Path>) -> Result<TriangleMesh, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut uvs = vec![];
+    let mut faces = vec![];
+
+    let mut library: HashMap<String, Material> = HashMap::new();
+    let mut materials = vec![default_material()];
+    let mut material_indexes: HashMap<String, usize> = HashMap::new();
+    let mut current_material = 0;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse {
+            path: path.to_path_buf(),
+            line: line_index + 1,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&args).map_err(error)?),
+            "vn" => normals.push(parse_vec3(&args).map_err(error)?),
+            "vt" => {
+                let uv = parse_floats(&args, 1).map_err(error)?;
+                uvs.push(Vec2::new(uv[0], uv.get(1).copied().unwrap_or(0.)));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error(format!("face needs at least 3 vertices, got {}", args.len())));
+                }
+                let counts = (positions.len(), normals.len(), uvs.len());
+                let vertices = args.iter()
+                    .map(|arg| parse_vertex(arg, counts))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                for i in 1..vertices.len() - 1 {
+                    faces.push(Face {
+                        vertices: [vertices[0], vertices[i], vertices[i + 1]],
+                        material: current_material,
+                    });
+                }
+            }
+            "mtllib" => {
+                for name in args {
+                    let mtl_path = path.parent().unwrap_or_else(|| Path::new("")).join(name);
+                    library.extend(load_mtl(&mtl_path)?);
+                }
+            }
+            "usemtl" => {
+                let name = args.first()
+                    .ok_or_else(|| error("usemtl without a material name".to_string()))?;
+                current_material = match material_indexes.get(*name) {
+                    Some(index) => *index,
+                    None => {
+                        let material = library.get(*name)
+                            .ok_or_else(|| error(format!("unknown material '{}'", name)))?;
+                        materials.push(material.clone().named(name));
+                        material_indexes.insert(name.to_string(), materials.len() - 1);
+                        materials.len() - 1
+                    }
+                };
+            }
+            // grouping, smoothing and the rest don't affect rendering
+            _ => {}
+        }
+    }
+
+    Ok(TriangleMesh::new(positions, normals, uvs, faces, materials))
+}
+
+#[derive(Default)]
+struct MtlEntry {
+    diffuse: Option<Vec3>,
+    diffuse_map: Option<Texture>,
+    specular: Option<Vec3>,
+    emission: Option<Vec3>,
+    shininess: Option<f32>,
+    refraction_index: Option<f32>,
+    dissolve: Option<f32>,
+    illum: Option<u32>,
+}
+
+/// Maps MTL parameters onto the closest `Material`:
+/// emissive (`Ke`) materials become lights, transparent ones (`d`/`Tr`, `illum` 4, 6, 7)
+/// become dielectrics, reflective ones (`illum` 3, 5) become metals and the rest are Lambertian,
+/// textured by `map_Kd` if present.
+impl From<MtlEntry> for Material {
+    fn from(entry: MtlEntry) -> Self {
+        let to_attenuation = |c: Vec3| Attenuation::new(
+            c.x.clamp(0., 1.),
+            c.y.clamp(0., 1.),
+            c.z.clamp(0., 1.),
+        );
+
+        if let Some(e) = entry.emission.filter(|e| e.max_element() > 0.) {
+            return Material::light(Intensity::new(e.x.max(0.), e.y.max(0.), e.z.max(0.)));
+        }
+
+        let transparent = entry.dissolve.is_some_and(|d| d < 1.)
+            || matches!(entry.illum, Some(4) | Some(6) | Some(7));
+        if transparent {
+            return Material::dielectric(entry.refraction_index.unwrap_or(1.5));
+        }
+
+        if matches!(entry.illum, Some(3) | Some(5)) {
+            let albedo = entry.specular.unwrap_or_else(|| Vec3::new(1., 1., 1.));
+            // Phong exponent to a perturbation radius, sharper highlights give less fuzz
+            let fuzz = entry.shininess.map_or(0., |ns| (2. / (ns.max(0.) + 2.)).sqrt());
+            return Material::metal(to_attenuation(albedo), fuzz);
+        }
+
+        match entry.diffuse_map {
+            Some(texture) => Material::lambertian(texture),
+            None => Material::lambertian(to_attenuation(entry.diffuse.unwrap_or_else(|| Vec3::new(0.8, 0.8, 0.8)))),
+        }
+    }
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let source = read(path)?;
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlEntry)> = None;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse {
+            path: path.to_path_buf(),
+            line: line_index + 1,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            let name = args.first()
+                .ok_or_else(|| error("newmtl without a material name".to_string()))?;
+            if let Some((name, entry)) = current.take() {
+                materials.insert(name, entry.into());
+            }
+            current = Some((name.to_string(), MtlEntry::default()));
+            continue;
+        }
+
+        let entry = match current.as_mut() {
+            Some((_, entry)) => entry,
+            None => return Err(error(format!("'{}' before any newmtl", keyword))),
+        };
+
+        match keyword {
+            "Kd" => entry.diffuse = Some(parse_vec3(&args).map_err(error)?),
+            "Ks" => entry.specular = Some(parse_vec3(&args).map_err(error)?),
+            "Ke" => entry.emission = Some(parse_vec3(&args).map_err(error)?),
+            "Ns" => entry.shininess = Some(parse_floats(&args, 1).map_err(error)?[0]),
+            "Ni" => entry.refraction_index = Some(parse_floats(&args, 1).map_err(error)?[0]),
+            "d" => entry.dissolve = Some(parse_floats(&args, 1).map_err(error)?[0]),
+            "Tr" => entry.dissolve = Some(1. - parse_floats(&args, 1).map_err(error)?[0]),
+            "illum" => entry.illum = Some(
+                args.first()
+                    .and_then(|arg| arg.parse().ok())
+                    .ok_or_else(|| error("illum expects an integer".to_string()))?
+            ),
+            "map_Kd" => {
+                // options such as `-s` precede the file name
+                let name = args.last()
+                    .ok_or_else(|| error("map_Kd without a file name".to_string()))?;
+                let texture_path = path.parent().unwrap_or_else(|| Path::new("")).join(name);
+                let image = ImageTexture::load(&texture_path, WrapMode::Repeat)
+                    .map_err(|e| error(e.to_string()))?;
+                entry.diffuse_map = Some(Texture::image(image));
+            }
+            // other maps and parameters have no counterpart yet
+            _ => {}
+        }
+    }
+
+    if let Some((name, entry)) = current {
+        materials.insert(name, entry.into());
+    }
+
+    Ok(materials)
+}
+
+fn default_material() -> Material {
+    Material::lambertian(Attenuation::new(0.5, 0.5, 0.5))
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    std::fs::read_to_string(path).map_err(|e| ObjError::Io(path.to_path_buf(), e))
+}
+
+fn parse_floats(args: &[&str], min_count: usize) -> Result<Vec<f32>, String> {
+    if args.len() < min_count {
+        return Err(format!("expected at least {} numbers, got {}", min_count, args.len()));
+    }
+    args.iter()
+        .map(|arg| arg.parse::<f32>().map_err(|_| format!("invalid number '{}'", arg)))
+        .collect()
+}
+
+fn parse_vec3(args: &[&str]) -> Result<Vec3, String> {
+    let xyz = parse_floats(args, 3)?;
+    Ok(Vec3::new(xyz[0], xyz[1], xyz[2]))
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolving negative (relative) indexes.
+fn parse_vertex(arg: &str, counts: (usize, usize, usize)) -> Result<Vertex, String> {
+    let (positions, normals, uvs) = counts;
+
+    let resolve = |index: &str, count: usize| -> Result<usize, String> {
+        let index = index.parse::<i64>().map_err(|_| format!("invalid index '{}' in '{}'", index, arg))?;
+        let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+        if resolved < 0 || resolved >= count as i64 {
+            Err(format!("index {} out of range in '{}'", index, arg))
+        } else {
+            Ok(resolved as usize)
+        }
+    };
+
+    let mut parts = arg.split('/');
+    let position = resolve(parts.next().unwrap(), positions)?;
+    let uv = match parts.next() {
+        Some(uv) if !uv.is_empty() => Some(resolve(uv, uvs)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(normal) if !normal.is_empty() => Some(resolve(normal, normals)?),
+        _ => None,
+    };
+
+    Ok(Vertex { position, normal, uv })
+}
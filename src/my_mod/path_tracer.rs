@@ -0,0 +1,286 @@
+use glam::Vec3;
+
+use crate::my_mod::hittable::{Accuracy, HitRecord};
+use crate::my_mod::integrator::{Integrator, Sample, SceneView};
+use crate::my_mod::light::Light;
+use crate::my_mod::material::ScatteringResult;
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::LightSample;
+use crate::my_mod::utils::random;
+use crate::my_mod::vec3;
+
+/// Kinds of bounces along a path, each with its own limit.
+#[derive(Copy, Clone)]
+enum Bounce {
+    /// Diffuse and glossy reflection, and scattering in media.
+    Diffuse,
+    /// Reflection into a discrete direction, like by a mirror.
+    Specular,
+    /// Passing through a surface.
+    Transmission,
+}
+
+impl Bounce {
+    fn classify(ray: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Bounce {
+        let normal = hit_record.normal();
+        let material = hit_record.material();
+        if !material.is_volume()
+            && Vec3::dot(ray.direction(), normal) * Vec3::dot(scattered.direction(), normal) > 0. {
+            Bounce::Transmission
+        } else if material.is_discrete() {
+            Bounce::Specular
+        } else {
+            Bounce::Diffuse
+        }
+    }
+}
+
+/// The direction density of the previous bounce, needed to weight emitters found by BSDF sampling.
+#[derive(Copy, Clone)]
+struct BsdfSample {
+    origin: Vec3,
+    pdf: f32,
+}
+
+/// Unidirectional path tracing with next-event estimation, the integrator giving the actual image.
+#[derive(Debug, Clone)]
+pub struct PathTracer {
+    max_depth: usize,
+    /// Limits of the bounces of each kind, indexed by `Bounce`.
+    max_bounces: [usize; 3],
+    roulette_depth: usize,
+    light_sampling: bool,
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        PathTracer {
+            max_depth: 20,
+            max_bounces: [usize::MAX; 3],
+            roulette_depth: 3,
+            light_sampling: true,
+        }
+    }
+}
+
+impl PathTracer {
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limits the diffuse and glossy bounces of a path, and scattering in media.
+    pub fn max_diffuse_depth(mut self, max_depth: usize) -> Self {
+        self.max_bounces[Bounce::Diffuse as usize] = max_depth;
+        self
+    }
+
+    /// Limits the mirror-like reflections of a path.
+    pub fn max_specular_depth(mut self, max_depth: usize) -> Self {
+        self.max_bounces[Bounce::Specular as usize] = max_depth;
+        self
+    }
+
+    /// Limits how often a path passes through surfaces.
+    pub fn max_transmission_depth(mut self, max_depth: usize) -> Self {
+        self.max_bounces[Bounce::Transmission as usize] = max_depth;
+        self
+    }
+
+    /// Ends paths at random from this many bounces on, the dimmer they are the likelier. The render
+    /// stays unbiased, only noisier in dark parts, while little light is lost to short paths.
+    pub fn roulette_depth(mut self, roulette_depth: usize) -> Self {
+        self.roulette_depth = roulette_depth;
+        self
+    }
+
+    /// Samples emissive objects directly at every diffuse bounce, combined with BSDF sampling by MIS.
+    /// Lights without a surface are sampled either way.
+    pub fn light_sampling(mut self, light_sampling: bool) -> Self {
+        self.light_sampling = light_sampling;
+        self
+    }
+}
+
+impl Integrator for PathTracer {
+    /// Follows a path from the camera, adding up the light reaching it at every vertex.
+    fn radiance(&self, scene: &SceneView, mut ray: Ray) -> Sample {
+        let mut radiance = vec3::zero();
+        let mut hit = true;
+        let mut throughput = Vec3::ONE;
+        let mut previous: Option<BsdfSample> = None;
+        let mut bounces = [0; 3];
+
+        for depth in 0..self.max_depth {
+            let surface = scene.bvh.hit_object(&ray, scene.accuracy);
+            // free-flight sampling: the ray scatters in the atmosphere if that happens before the surface
+            let scattering = scene.atmosphere.and_then(|atmosphere| {
+                atmosphere.hit(&ray, surface.as_ref().map_or(f32::INFINITY, |(_, hit)| hit.t()))
+            });
+            let (index, hit_record) = match (scattering, surface) {
+                (Some(hit), _) => (None, hit),
+                (None, Some((index, hit))) => (Some(index), hit),
+                (None, None) => {
+                    hit = depth > 0;
+                    // a transparent background is left out where the camera sees it directly
+                    if hit || !scene.transparent {
+                        radiance += throughput * self.escaped(scene, &ray, previous);
+                    }
+                    break;
+                }
+            };
+            let material = hit_record.material();
+            let emitted = material.emitted(&hit_record);
+            let emitted = match (previous, index) {
+                // this emitter could also have been reached by light sampling from the previous hit
+                (Some(BsdfSample { origin, pdf }), Some(index)) if self.light_sampling && emitted != vec3::zero() => {
+                    let light_pdf = scene.objects[index].pdf_towards(origin, ray.direction(), ray.time())
+                        / self.light_count(scene) as f32;
+                    emitted * power_heuristic(pdf, light_pdf)
+                }
+                _ => emitted,
+            };
+
+            // direct lighting doesn't depend on the scattered ray, which may also be absorbed
+            let discrete = material.is_discrete();
+            let direct = if discrete {
+                vec3::zero()
+            } else {
+                self.sample_light(scene, &ray, &hit_record)
+            };
+            radiance += throughput * (emitted + direct);
+
+            let (attenuation, scattered) = match material.scatter(&ray, &hit_record) {
+                ScatteringResult::ScatterredRay(attenuation, scattered) => (attenuation, scattered),
+                ScatteringResult::None => break,
+            };
+            let bounce = Bounce::classify(&ray, &hit_record, &scattered) as usize;
+            bounces[bounce] += 1;
+            if bounces[bounce] > self.max_bounces[bounce] {
+                break;
+            }
+
+            throughput *= attenuation;
+            // past the first few bounces, dim paths are ended at random and the survivors brightened
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.max_element().min(1.);
+                if random::<f32>() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+
+            previous = if self.light_sampling && !discrete {
+                material.evaluate(ray.direction(), &hit_record, scattered.direction())
+                    .map(|(_, pdf)| BsdfSample { origin: hit_record.point(), pdf })
+            } else {
+                None
+            };
+            ray = scattered;
+        }
+        Sample { radiance, hit }
+    }
+}
+
+impl PathTracer {
+    /// Direct lighting from a randomly picked light, emitters are weighted against BSDF sampling.
+    fn sample_light(&self, scene: &SceneView, ray: &Ray, hit_record: &HitRecord) -> Vec3 {
+        let count = self.light_count(scene);
+        if count == 0 {
+            return vec3::zero();
+        }
+
+        // lights come first, then the emissive objects and the environment as one more
+        let choice = random::<usize>() % count;
+        if let Some(light) = scene.lights.get(choice) {
+            return count as f32 * self.illuminate(scene, light.as_ref(), ray, hit_record);
+        }
+        let light = scene.emitters.get(choice - scene.lights.len()).map(|index| &scene.objects[*index]);
+        let sample = match light {
+            Some(light) => light.sample_towards(hit_record.point(), ray.time()),
+            None => scene.environment.and_then(|environment| environment.sample())
+                .map(|(direction, pdf)| LightSample { direction, distance: f32::INFINITY, pdf }),
+        };
+        let sample = match sample {
+            Some(sample) if sample.pdf > 0. => sample,
+            _ => return vec3::zero(),
+        };
+        let (reflectance, bsdf_pdf) = match hit_record.material().evaluate(ray.direction(), hit_record, sample.direction) {
+            Some((reflectance, pdf)) if reflectance != vec3::zero() => (reflectance, pdf),
+            _ => return vec3::zero(),
+        };
+
+        let shadow_ray = Ray::new(&hit_record.point(), &sample.direction, ray.time());
+        let emitted = match light {
+            // the radiance depends on the point and the side of the light that is seen
+            Some(light) => match light.hit(&shadow_ray, &Accuracy { min: scene.accuracy.min, max: f32::INFINITY }) {
+                Some(light_hit) => light_hit.material().emitted(&light_hit),
+                None => return vec3::zero(),
+            },
+            None => scene.environment.map_or(vec3::zero(), |environment| environment.radiance(sample.direction)),
+        };
+        if emitted == vec3::zero() {
+            return vec3::zero();
+        }
+
+        let transmittance = scene.visibility(&shadow_ray, sample.distance);
+        if transmittance == 0. {
+            return vec3::zero();
+        }
+
+        let light_pdf = sample.pdf / count as f32;
+        transmittance * emitted * reflectance * power_heuristic(light_pdf, bsdf_pdf) / light_pdf
+    }
+
+    /// Direct lighting from a light without a surface, which BSDF sampling can't find.
+    fn illuminate(&self, scene: &SceneView, light: &dyn Light, ray: &Ray, hit_record: &HitRecord) -> Vec3 {
+        let illumination = match light.illuminate(hit_record.point()) {
+            Some(illumination) if illumination.irradiance != vec3::zero() => illumination,
+            _ => return vec3::zero(),
+        };
+        let reflectance = match hit_record.material().evaluate(ray.direction(), hit_record, illumination.direction) {
+            Some((reflectance, _)) if reflectance != vec3::zero() => reflectance,
+            _ => return vec3::zero(),
+        };
+        let shadow_ray = Ray::new(&hit_record.point(), &illumination.direction, ray.time());
+        scene.visibility(&shadow_ray, illumination.distance) * illumination.irradiance * reflectance
+    }
+
+    /// Light arriving along a ray that leaves the scene.
+    fn escaped(&self, scene: &SceneView, ray: &Ray, previous: Option<BsdfSample>) -> Vec3 {
+        let environment = match scene.environment {
+            Some(environment) => environment,
+            None => return scene.background,
+        };
+        let radiance = environment.radiance(ray.direction());
+        match previous {
+            // the environment could also have been reached by light sampling from the previous hit
+            Some(BsdfSample { pdf, .. }) if self.light_sampling => {
+                let light_pdf = environment.pdf(ray.direction()) / self.light_count(scene) as f32;
+                radiance * power_heuristic(pdf, light_pdf)
+            }
+            _ => radiance,
+        }
+    }
+
+    /// Choices of `sample_light`, each picked with the same chance: the lights and, unless they are
+    /// left to BSDF sampling, the emissive objects and the environment.
+    fn light_count(&self, scene: &SceneView) -> usize {
+        let sampled = if self.light_sampling {
+            scene.emitters.len() + scene.environment.is_some() as usize
+        } else {
+            0
+        };
+        scene.lights.len() + sampled
+    }
+}
+
+/// Multiple importance sampling weight of a strategy with density `a` against one with density `b`.
+fn power_heuristic(a: f32, b: f32) -> f32 {
+    let (a, b) = (a * a, b * b);
+    if a + b == 0. {
+        0.
+    } else {
+        a / (a + b)
+    }
+}
@@ -0,0 +1,82 @@
+use glam::Vec3;
+
+use crate::my_mod::utils::{random, random_on_unit_sphere};
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise over random unit vectors at the lattice points.
+#[derive(Debug)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    permutation_x: Vec<usize>,
+    permutation_y: Vec<usize>,
+    permutation_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new() -> Self {
+        Self {
+            gradients: (0..POINT_COUNT).map(|_| random_on_unit_sphere()).collect(),
+            permutation_x: permutation(),
+            permutation_y: permutation(),
+            permutation_z: permutation(),
+        }
+    }
+
+    /// Smooth noise within about `[-1, 1]`.
+    pub fn noise(&self, point: Vec3) -> f32 {
+        let floor = point.floor();
+        let fraction = point - floor;
+        let (i, j, k) = (floor.x as i64, floor.y as i64, floor.z as i64);
+
+        // Hermite smoothing hides the lattice
+        let smooth = fraction * fraction * (Vec3::new(3., 3., 3.) - 2. * fraction);
+
+        let mut accumulated = 0.;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.permutation_x[((i + di) & 255) as usize]
+                        ^ self.permutation_y[((j + dj) & 255) as usize]
+                        ^ self.permutation_z[((k + dk) & 255) as usize];
+                    let corner = Vec3::new(di as f32, dj as f32, dk as f32);
+                    let weight = corner * smooth + (Vec3::ONE - corner) * (Vec3::ONE - smooth);
+                    accumulated += weight.x * weight.y * weight.z
+                        * Vec3::dot(self.gradients[index], fraction - corner);
+                }
+            }
+        }
+
+        accumulated
+    }
+
+    /// Absolute value of the sum of `depth` octaves of noise, each twice the frequency and half the amplitude.
+    pub fn turbulence(&self, point: Vec3, depth: usize) -> f32 {
+        let mut accumulated = 0.;
+        let mut point = point;
+        let mut weight = 1.;
+
+        for _ in 0..depth {
+            accumulated += weight * self.noise(point);
+            weight *= 0.5;
+            point *= 2.;
+        }
+
+        accumulated.abs()
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn permutation() -> Vec<usize> {
+    let mut permutation = (0..POINT_COUNT).collect::<Vec<_>>();
+    for i in (1..POINT_COUNT).rev() {
+        let target = random::<usize>() % (i + 1);
+        permutation.swap(i, target);
+    }
+    permutation
+}
@@ -0,0 +1,92 @@
+use glam::{Vec2, Vec3};
+
+use crate::my_mod::bbox::BBox;
+use crate::my_mod::bvh::Boundable;
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::material::Material;
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::SceneObject;
+use crate::my_mod::time::Shutter;
+use crate::my_mod::vec3;
+
+/// An infinite plane through `point`, facing `normal`.
+///
+/// Planes have no finite bounds, so they are tested against every ray instead of being put in the BVH.
+#[derive(Debug)]
+pub struct Plane {
+    point: Vec3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Material,
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, material: Material) -> Plane {
+        assert!(normal.length() > 0.);
+        let normal = normal.normalize();
+        let (tangent, bitangent) = vec3::orthonormal_basis(normal);
+        Plane {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+}
+
+/// Distance along `ray` to the plane through `point` with `normal`, if within `accuracy`.
+pub(crate) fn intersect_plane(ray: &Ray, point: Vec3, normal: Vec3, accuracy: &Accuracy) -> Option<f32> {
+    let denominator = Vec3::dot(normal, ray.direction());
+    if denominator.abs() < 1e-8 {
+        return None;
+    }
+
+    let t = Vec3::dot(normal, point - ray.origin()) / denominator;
+    let Accuracy { min, max } = *accuracy;
+    if t < min || max < t {
+        None
+    } else {
+        Some(t)
+    }
+}
+
+/// The side of the surface the ray comes from, and the normal turned towards it.
+pub(crate) fn face_normal(ray: &Ray, outward_normal: Vec3) -> (bool, Vec3) {
+    let front = Vec3::dot(ray.direction(), outward_normal) < 0.;
+    if front {
+        (true, outward_normal)
+    } else {
+        (false, -outward_normal)
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        let t = intersect_plane(ray, self.point, self.normal, accuracy)?;
+        let point = ray.at(t);
+        let (front, normal) = face_normal(ray, self.normal);
+
+        // world units along the tangents, textures repeat every unit
+        let offset = point - self.point;
+        let uv = Vec2::new(Vec3::dot(offset, self.tangent), Vec3::dot(offset, self.bitangent));
+
+        Some(HitRecord::new(point, normal, uv, &self.material, t, front).dpdu(self.tangent))
+    }
+}
+
+impl Boundable for Plane {
+    fn bbox(&self, _shutter: &Shutter) -> BBox {
+        BBox {
+            min: Vec3::splat(f32::NEG_INFINITY),
+            max: Vec3::splat(f32::INFINITY),
+        }
+    }
+}
+
+impl SceneObject for Plane {
+    fn is_bounded(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,233 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3};
+
+use crate::my_mod::hittable::HitRecord;
+use crate::my_mod::material::Attenuation;
+use crate::my_mod::microfacet::{Frame, Ggx};
+use crate::my_mod::texture::Texture;
+use crate::my_mod::utils::random;
+use crate::my_mod::vec3;
+
+/// Roughness is kept above this so the specular lobes stay within what GGX evaluates well.
+const MIN_ROUGHNESS: f32 = 0.05;
+
+/// One material for most surfaces, after the Disney principled BSDF.
+///
+/// A diffuse base with sheen, a GGX specular layer tinted towards the base color as it gets
+/// `metallic`, rough glass for `transmission` and a clear coat on top. Every parameter is a
+/// texture, scalar ones read its gray value.
+#[derive(Debug, Clone)]
+pub struct Principled {
+    base_color: Texture,
+    metallic: Texture,
+    roughness: Texture,
+    /// Reflectance of the dielectric specular layer at normal incidence, 1 is 8%.
+    specular: Texture,
+    sheen: Texture,
+    sheen_tint: Texture,
+    clearcoat: Texture,
+    clearcoat_roughness: Texture,
+    transmission: Texture,
+    refraction_index: f32,
+}
+
+fn gray(value: f32) -> Texture {
+    Texture::solid(Attenuation::new(value, value, value))
+}
+
+impl Principled {
+    pub fn new(base_color: impl Into<Texture>) -> Principled {
+        Principled {
+            base_color: base_color.into(),
+            metallic: gray(0.),
+            roughness: gray(0.5),
+            specular: gray(0.5),
+            sheen: gray(0.),
+            sheen_tint: gray(0.5),
+            clearcoat: gray(0.),
+            clearcoat_roughness: gray(0.03),
+            transmission: gray(0.),
+            refraction_index: 1.5,
+        }
+    }
+
+    pub fn metallic(mut self, metallic: impl Into<Texture>) -> Self {
+        self.metallic = metallic.into();
+        self
+    }
+
+    pub fn roughness(mut self, roughness: impl Into<Texture>) -> Self {
+        self.roughness = roughness.into();
+        self
+    }
+
+    pub fn specular(mut self, specular: impl Into<Texture>) -> Self {
+        self.specular = specular.into();
+        self
+    }
+
+    /// Extra reflection at grazing angles, as on cloth, tinted towards the base color by `tint`.
+    pub fn sheen(mut self, sheen: impl Into<Texture>, tint: impl Into<Texture>) -> Self {
+        self.sheen = sheen.into();
+        self.sheen_tint = tint.into();
+        self
+    }
+
+    pub fn clearcoat(mut self, clearcoat: impl Into<Texture>, roughness: impl Into<Texture>) -> Self {
+        self.clearcoat = clearcoat.into();
+        self.clearcoat_roughness = roughness.into();
+        self
+    }
+
+    pub fn transmission(mut self, transmission: impl Into<Texture>, refraction_index: f32) -> Self {
+        assert!(refraction_index > 0.);
+        self.transmission = transmission.into();
+        self.refraction_index = refraction_index;
+        self
+    }
+
+    /// The base color at the hit point.
+    pub(crate) fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        color(&self.base_color, hit_record)
+    }
+
+    /// BSDF times cosine from travelling along `incoming` into `direction`, and the density of `sample`.
+    pub(crate) fn evaluate(&self, incoming: Vec3, hit_record: &HitRecord, direction: Vec3) -> (Vec3, f32) {
+        let frame = Frame::new(hit_record.normal(), hit_record.tangent());
+        self.lobes(hit_record).evaluate(frame.to_local(-incoming), frame.to_local(direction))
+    }
+
+    /// Picks a lobe, then a direction from it, and returns that direction with its BSDF times cosine over density.
+    pub(crate) fn sample(&self, incoming: Vec3, hit_record: &HitRecord) -> Option<(Vec3, Vec3)> {
+        let frame = Frame::new(hit_record.normal(), hit_record.tangent());
+        let lobes = self.lobes(hit_record);
+        let wo = frame.to_local(-incoming);
+        if wo.z <= 0. {
+            return None;
+        }
+
+        let [diffuse, specular, clearcoat, _] = lobes.probabilities();
+        let pick = random::<f32>();
+        let wi = if pick < diffuse {
+            let (r, phi) = (random::<f32>().sqrt(), 2. * PI * random::<f32>());
+            Some(Vec3::new(r * phi.cos(), r * phi.sin(), (1. - r * r).max(0.).sqrt()))
+        } else if pick < diffuse + specular {
+            lobes.specular.sample_reflection(wo)
+        } else if pick < diffuse + specular + clearcoat {
+            lobes.clearcoat.sample_reflection(wo)
+        } else {
+            lobes.specular.sample_dielectric(wo, lobes.ratio)
+        }?;
+
+        let (value, pdf) = lobes.evaluate(wo, wi);
+        if pdf <= 0. {
+            return None;
+        }
+        Some((value / pdf, frame.to_world(wi)))
+    }
+
+    fn lobes(&self, hit_record: &HitRecord) -> Lobes {
+        let base = color(&self.base_color, hit_record);
+        let metallic = scalar(&self.metallic, hit_record);
+        let transmission = scalar(&self.transmission, hit_record);
+        let roughness = scalar(&self.roughness, hit_record).max(MIN_ROUGHNESS);
+        let clearcoat_roughness = scalar(&self.clearcoat_roughness, hit_record).max(MIN_ROUGHNESS);
+
+        let luminance = Vec3::dot(base, Vec3::new(0.2126, 0.7152, 0.0722));
+        let tint = if luminance > 0. { base / luminance } else { Vec3::ONE };
+        let sheen_tint = scalar(&self.sheen_tint, hit_record);
+
+        Lobes {
+            base,
+            specular_color: Vec3::splat(0.08 * scalar(&self.specular, hit_record)).lerp(base, metallic),
+            sheen_color: scalar(&self.sheen, hit_record) * Vec3::ONE.lerp(tint, sheen_tint),
+            // light passing through a closed object crosses two surfaces and gets the base color once
+            transmission_color: vec3::sqrt(base),
+            diffuse_weight: (1. - metallic) * (1. - transmission),
+            specular_weight: 1. - (1. - metallic) * transmission,
+            transmission_weight: (1. - metallic) * transmission,
+            clearcoat_weight: 0.25 * scalar(&self.clearcoat, hit_record),
+            specular: Ggx::new(Vec2::splat(roughness)),
+            clearcoat: Ggx::new(Vec2::splat(clearcoat_roughness)),
+            ratio: if hit_record.front() { 1. / self.refraction_index } else { self.refraction_index },
+        }
+    }
+}
+
+/// The parameters at one point, directions are local to the surface.
+struct Lobes {
+    base: Vec3,
+    specular_color: Vec3,
+    sheen_color: Vec3,
+    /// Tint of the refracted light at each surface it crosses.
+    transmission_color: Vec3,
+    diffuse_weight: f32,
+    specular_weight: f32,
+    transmission_weight: f32,
+    clearcoat_weight: f32,
+    specular: Ggx,
+    clearcoat: Ggx,
+    ratio: f32,
+}
+
+impl Lobes {
+    /// Chances of sampling the diffuse, specular, clear coat and transmission lobes.
+    fn probabilities(&self) -> [f32; 4] {
+        let weights = [self.diffuse_weight, self.specular_weight, self.clearcoat_weight, self.transmission_weight];
+        let total = weights.iter().sum::<f32>();
+        weights.map(|weight| weight / total)
+    }
+
+    fn evaluate(&self, wo: Vec3, wi: Vec3) -> (Vec3, f32) {
+        if wo.z <= 0. || wi.z == 0. {
+            return (Vec3::ZERO, 0.);
+        }
+        let [diffuse_chance, specular_chance, clearcoat_chance, transmission_chance] = self.probabilities();
+        // light reaching the layers below the coat and the diffuse base below the specular layer
+        let below_clearcoat = 1. - self.clearcoat_weight * schlick(Vec3::splat(0.04), wo.z).x;
+        let below_specular = 1. - schlick(self.specular_color, wo.z).max_element();
+
+        let mut value = Vec3::ZERO;
+        let mut pdf = 0.;
+        if wi.z > 0. {
+            let h = (wo + wi).normalize();
+            let cos_h = Vec3::dot(wo, h);
+
+            let sheen = self.sheen_color * (1. - Vec3::dot(wi, h)).max(0.).powi(5);
+            value += below_clearcoat * self.diffuse_weight * (below_specular * self.base / PI + sheen) * wi.z;
+            pdf += diffuse_chance * wi.z / PI;
+
+            let (specular, specular_pdf) = self.specular.reflection(wo, wi);
+            value += below_clearcoat * self.specular_weight * schlick(self.specular_color, cos_h) * specular;
+            pdf += specular_chance * specular_pdf;
+
+            let (clearcoat, clearcoat_pdf) = self.clearcoat.reflection(wo, wi);
+            value += self.clearcoat_weight * schlick(Vec3::splat(0.04), cos_h) * clearcoat;
+            pdf += clearcoat_chance * clearcoat_pdf;
+        }
+
+        if self.transmission_weight > 0. {
+            let (transmission, transmission_pdf) = self.specular.dielectric(wo, wi, self.ratio);
+            // only the refracted light takes on the base color
+            let tint = if wi.z < 0. { self.transmission_color } else { Vec3::ONE };
+            value += below_clearcoat * self.transmission_weight * tint * transmission;
+            pdf += transmission_chance * transmission_pdf;
+        }
+
+        (value, pdf)
+    }
+}
+
+fn schlick(f0: Vec3, cos: f32) -> Vec3 {
+    f0 + (Vec3::ONE - f0) * (1. - cos.clamp(0., 1.)).powi(5)
+}
+
+fn color(texture: &Texture, hit_record: &HitRecord) -> Vec3 {
+    texture.value(hit_record.uv(), hit_record.point()).into()
+}
+
+fn scalar(texture: &Texture, hit_record: &HitRecord) -> f32 {
+    let color = color(texture, hit_record);
+    (color.x + color.y + color.z) / 3.
+}
@@ -0,0 +1,118 @@
+use glam::{Vec2, Vec3};
+
+use crate::my_mod::bbox::BBox;
+use crate::my_mod::bvh::Boundable;
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::material::Material;
+use crate::my_mod::plane::{face_normal, intersect_plane};
+use crate::my_mod::ray::Ray;
+use crate::my_mod::scene::{LightSample, SceneObject};
+use crate::my_mod::time::{Shutter, TimePoint};
+use crate::my_mod::utils::random;
+
+/// The parallelogram spanned by `u` and `v` from `corner`, facing `u × v`.
+#[derive(Debug)]
+pub struct Quad {
+    corner: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    /// `(u × v) / |u × v|²`, projects hit points onto the edges.
+    w: Vec3,
+    area: f32,
+    material: Material,
+}
+
+impl Quad {
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3, material: Material) -> Quad {
+        let n = Vec3::cross(u, v);
+        assert!(n.length() > 0., "quad edges must not be parallel");
+        Quad {
+            corner,
+            u,
+            v,
+            normal: n.normalize(),
+            w: n / n.length_squared(),
+            area: n.length(),
+            material,
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        self.area
+    }
+
+    /// Converts the uniform area density to solid angle as seen along `direction`.
+    fn area_pdf(&self, direction: Vec3, distance: f32) -> f32 {
+        let cos = Vec3::dot(self.normal, direction).abs();
+        if cos < 1e-6 {
+            return 0.;
+        }
+        distance.powi(2) / (cos * self.area)
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        let t = intersect_plane(ray, self.corner, self.normal, accuracy)?;
+        let point = ray.at(t);
+
+        let planar = point - self.corner;
+        let alpha = Vec3::dot(self.w, Vec3::cross(planar, self.v));
+        let beta = Vec3::dot(self.w, Vec3::cross(self.u, planar));
+        if !(0. ..=1.).contains(&alpha) || !(0. ..=1.).contains(&beta) {
+            return None;
+        }
+
+        let (front, normal) = face_normal(ray, self.normal);
+        Some(HitRecord::new(point, normal, Vec2::new(alpha, beta), &self.material, t, front).dpdu(self.u))
+    }
+}
+
+impl Boundable for Quad {
+    fn bbox(&self, _shutter: &Shutter) -> BBox {
+        let corners = [self.corner + self.u, self.corner + self.v, self.corner + self.u + self.v];
+        let (min, max) = corners.iter().fold((self.corner, self.corner), |(min, max), c| (min.min(*c), max.max(*c)));
+        // flat quads still need some thickness for the slab test
+        let eps = Vec3::splat(1e-4);
+        BBox {
+            min: min - eps,
+            max: max + eps,
+        }
+    }
+}
+
+impl SceneObject for Quad {
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_towards(&self, origin: Vec3, _time: TimePoint) -> Option<LightSample> {
+        let point = self.corner + random::<f32>() * self.u + random::<f32>() * self.v;
+
+        let to_point = point - origin;
+        let distance = to_point.length();
+        if distance == 0. {
+            return None;
+        }
+        let direction = to_point / distance;
+        let pdf = self.area_pdf(direction, distance);
+        if pdf == 0. {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            pdf,
+        })
+    }
+
+    fn pdf_towards(&self, origin: Vec3, direction: Vec3, time: TimePoint) -> f32 {
+        let ray = Ray::new(&origin, &direction, time);
+        match self.hit(&ray, &Accuracy { min: 1e-4, max: f32::INFINITY }) {
+            Some(hit) => self.area_pdf(ray.direction(), hit.t()),
+            None => 0.,
+        }
+    }
+}
@@ -1,16 +1,20 @@
 use glam::Vec3;
 
+use crate::my_mod::time::TimePoint;
+
 #[derive(Debug)]
 pub struct Ray {
     orig: Vec3,
     dir: Vec3,
+    time: TimePoint,
 }
 
 impl Ray {
-    pub fn new(orig: &Vec3, dir: &Vec3) -> Ray {
+    pub fn new(orig: &Vec3, dir: &Vec3, time: TimePoint) -> Ray {
         Ray {
             orig: *orig,
             dir: dir.normalize(),
+            time,
         }
     }
 
@@ -22,6 +26,11 @@ impl Ray {
         self.dir
     }
 
+    /// The moment the ray samples, moving objects are intersected where they are at that time.
+    pub fn time(&self) -> TimePoint {
+        self.time
+    }
+
     pub fn at(&self, t: f32) -> Vec3 {
         self.orig + t * self.dir
     }
@@ -1,28 +1,41 @@
-use glam::Vec3;
-use rand::random;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
-use crate::my_mod::bvh::BVHScene;
+use crate::my_mod::bvh::{BVHScene, SplitStrategy, TLAS};
 use crate::my_mod::camera::Camera;
-use crate::my_mod::hittable::{Accuracy, Hittable};
-use crate::my_mod::image::Image;
+use crate::my_mod::environment::Environment;
+use crate::my_mod::hittable::Accuracy;
+use crate::my_mod::image::HdrImage;
+use crate::my_mod::integrator::{
+    AmbientOcclusion, BvhCost, Integrator, IntegratorKind, MaterialId, SceneView, SurfaceProperty, SurfaceView,
+};
 use crate::my_mod::intensity::Intensity;
-use crate::my_mod::material::ScatteringResult;
-use crate::my_mod::ray::Ray;
-use crate::my_mod::resolution::Resolution;
-use crate::my_mod::rgb::RGB;
+use crate::my_mod::medium::Atmosphere;
+use crate::my_mod::path_tracer::PathTracer;
+use crate::my_mod::resolution::{CropWindow, Resolution};
 use crate::my_mod::scene::Scene;
-use crate::my_mod::time::{TimeInterval, TimePoint};
-use crate::my_mod::utils::random_from;
+use crate::my_mod::utils::{random, reseed};
 use crate::my_mod::vec3;
 
 pub struct Renderer {
     samples_per_pixel: usize,
     accuracy: Accuracy,
-    max_depth: usize,
+    integrator: IntegratorKind,
+    path_tracer: PathTracer,
+    ao_distance: Option<f32>,
     camera: Camera,
     background: Intensity,
+    environment: Option<Environment>,
     resolution: Resolution,
+    crop: Option<CropWindow>,
+    split_strategy: SplitStrategy,
+    atmosphere: Option<Atmosphere>,
+    seed: Option<u64>,
+    alpha: bool,
+    report_progress: bool,
 }
 
 impl Renderer {
@@ -30,107 +43,254 @@ impl Renderer {
         Self {
             samples_per_pixel: 100,
             accuracy: Accuracy { min: 0.001, max: f32::INFINITY },
-            max_depth: 20,
+            integrator: IntegratorKind::default(),
+            path_tracer: PathTracer::default(),
+            ao_distance: None,
             camera,
             background: Intensity::new(1., 1., 1.),
+            environment: None,
             resolution,
+            crop: None,
+            split_strategy: SplitStrategy::default(),
+            atmosphere: None,
+            seed: None,
+            alpha: false,
+            report_progress: false,
         }
     }
 
+    /// Changes the output resolution, keeping the camera's vertical field of view.
+    pub fn resolution(mut self, resolution: Resolution) -> Self {
+        self.camera.set_aspect_ratio(resolution.width as f32 / resolution.height as f32);
+        self.resolution = resolution;
+        self
+    }
+
+    pub fn image_resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Renders only the given part of the image, the result has the size of the window.
+    pub fn crop(mut self, crop: CropWindow) -> Self {
+        let Resolution { width, height } = self.resolution;
+        assert!(crop.x0 < crop.x1 && crop.x1 <= width);
+        assert!(crop.y0 < crop.y1 && crop.y1 <= height);
+        self.crop = Some(crop);
+        self
+    }
+
+    pub fn split_strategy(mut self, split_strategy: SplitStrategy) -> Self {
+        self.split_strategy = split_strategy;
+        self
+    }
+
+    /// Samples emissive objects directly at every diffuse bounce, combined with BSDF sampling by MIS.
+    /// Lights without a surface are sampled either way.
+    pub fn light_sampling(mut self, light_sampling: bool) -> Self {
+        self.path_tracer = self.path_tracer.light_sampling(light_sampling);
+        self
+    }
+
+    /// Radiance of rays leaving the scene, white by default.
+    pub fn background(mut self, background: Intensity) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Lights the scene with an environment map instead of the constant background.
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Fills the space between objects with a homogeneous medium.
+    pub fn atmosphere(mut self, atmosphere: Atmosphere) -> Self {
+        self.atmosphere = Some(atmosphere);
+        self
+    }
+
+    /// Makes the render reproducible, every pixel draws its samples from its own seeded generator.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Attaches to the image the share of camera samples per pixel that hit geometry, and leaves the
+    /// background out of the colors, which are then premultiplied by it.
+    pub fn alpha(mut self, alpha: bool) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Prints the BVH build time and the share of finished rows to stderr while rendering.
+    pub fn report_progress(mut self, report_progress: bool) -> Self {
+        self.report_progress = report_progress;
+        self
+    }
+
     pub fn samples_per_pixel(mut self, samples_per_pixel: usize) -> Self {
         self.samples_per_pixel = samples_per_pixel;
         self
     }
 
     pub fn max_depth(mut self, max_depth: usize) -> Self {
-        self.max_depth = max_depth;
+        self.path_tracer = self.path_tracer.max_depth(max_depth);
         self
     }
 
-    pub fn render(&self, scene: &Scene) -> Image {
+    /// Limits the diffuse and glossy bounces of a path, and scattering in media.
+    pub fn max_diffuse_depth(mut self, max_depth: usize) -> Self {
+        self.path_tracer = self.path_tracer.max_diffuse_depth(max_depth);
+        self
+    }
+
+    /// Limits the mirror-like reflections of a path.
+    pub fn max_specular_depth(mut self, max_depth: usize) -> Self {
+        self.path_tracer = self.path_tracer.max_specular_depth(max_depth);
+        self
+    }
+
+    /// Limits how often a path passes through surfaces.
+    pub fn max_transmission_depth(mut self, max_depth: usize) -> Self {
+        self.path_tracer = self.path_tracer.max_transmission_depth(max_depth);
+        self
+    }
+
+    /// See `PathTracer::roulette_depth`.
+    pub fn roulette_depth(mut self, roulette_depth: usize) -> Self {
+        self.path_tracer = self.path_tracer.roulette_depth(roulette_depth);
+        self
+    }
+
+    /// Renders a debugging view of the scene instead of its light, the path settings apply to `Path` only.
+    pub fn integrator(mut self, integrator: IntegratorKind) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// How far ambient occlusion looks for blockers, a tenth of the size of the bounded objects by default.
+    pub fn ao_distance(mut self, ao_distance: f32) -> Self {
+        assert!(ao_distance > 0., "occlusion distance must be positive");
+        self.ao_distance = Some(ao_distance);
+        self
+    }
+
+    /// Builds the top-level BVH of the scene for `render`, with the split strategy and shutter
+    /// of this renderer.
+    pub fn tlas(&self, scene: &Scene) -> TLAS {
+        if let Some(seed) = self.seed {
+            reseed(seed);
+        }
+        let started = Instant::now();
+        let tlas = TLAS::new(scene, self.split_strategy, self.camera.shutter());
+        if self.report_progress {
+            eprintln!("built {:?} BVH in {:.2?}", self.split_strategy, started.elapsed());
+        }
+        tlas
+    }
+
+    /// Renders the linear radiance of the scene, see `HdrImage::to_display` for viewing it.
+    ///
+    /// `tlas` comes from `Renderer::tlas` for this scene, refit if its instances moved since.
+    pub fn render(&self, scene: &Scene, tlas: &TLAS) -> HdrImage {
         let Resolution { width, height } = self.resolution;
-        let bvh = BVHScene::new(scene);
+        let CropWindow { x0, y0, x1, y1 } = self.crop.unwrap_or(CropWindow {
+            x0: 0,
+            y0: 0,
+            x1: width,
+            y1: height,
+        });
+
+        let bvh = BVHScene::new(tlas, scene);
+        let Scene { objects, lights } = scene;
+        let integrator = self.build_integrator(&bvh);
+        let view = SceneView {
+            bvh,
+            objects,
+            emitters: (0..objects.len()).filter(|i| objects[*i].is_emissive()).collect(),
+            lights,
+            background: self.background.into(),
+            environment: self.environment.as_ref(),
+            accuracy: &self.accuracy,
+            atmosphere: self.atmosphere.as_ref(),
+            transparent: self.alpha,
+        };
 
-        let content = (0..height).into_par_iter().map(|row| {
-            let mut row_pixels = vec![RGB::black(); width];
+        let rows_done = AtomicUsize::new(0);
+        let rows_total = y1 - y0;
+
+        let content = (y0..y1).into_par_iter().map(|row| {
+            let mut row_pixels = vec![(vec3::zero(), 1.); x1 - x0];
+
+            for (col, (pixel, coverage)) in (x0..x1).zip(row_pixels.iter_mut()) {
+                if let Some(seed) = self.seed {
+                    let pixel_index = (row * width + col) as u64;
+                    reseed(seed ^ pixel_index.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                }
 
-            for col in 0..width {
                 let mut result_intensity = vec3::zero();
+                let mut hits = 0;
 
                 for _ in 0..self.samples_per_pixel {
                     let u = ((col as f32) + random::<f32>()) / (width - 1) as f32;
                     let v = ((height - row - 1) as f32 + random::<f32>()) / (height - 1) as f32;
                     let ray = self.camera.get_ray(u, v);
-                    let intense: Vec3 = ray_intensity(
-                        &bvh,
-                        &self.background,
-                        &ray,
-                        &self.accuracy,
-                        self.max_depth
-                    ).into();
-                    result_intensity += intense;
+                    let sample = integrator.radiance(&view, ray);
+                    result_intensity += sample.radiance;
+                    hits += sample.hit as usize;
+                }
+                *pixel = result_intensity / self.samples_per_pixel as f32;
+                if self.alpha {
+                    *coverage = hits as f32 / self.samples_per_pixel as f32;
+                }
+            }
+
+            if self.report_progress {
+                let done = rows_done.fetch_add(1, Ordering::Relaxed) + 1;
+                if done * 100 / rows_total != (done - 1) * 100 / rows_total {
+                    eprint!("\rrendering: {:3}%", done * 100 / rows_total);
+                    std::io::stderr().flush().ok();
                 }
-                result_intensity /= self.samples_per_pixel as f32;
-
-                // gamma-correction
-                let result_intensity = Vec3::new(
-                    result_intensity.x.sqrt(),
-                    result_intensity.y.sqrt(),
-                    result_intensity.z.sqrt(),
-                );
-
-                let rgb = RGB::new(
-                    result_intensity.x.clamp(0., 1.),
-                    result_intensity.y.clamp(0., 1.),
-                    result_intensity.z.clamp(0., 1.),
-                );
-
-                row_pixels[col] = rgb;
             }
 
             row_pixels
-        }).flat_map(|x| x).collect();
+        }).flat_map(|x| x).collect::<Vec<_>>();
+
+        if self.report_progress {
+            eprintln!();
+        }
 
-        Image::new_with_content(
+        let (content, coverage): (Vec<_>, Vec<_>) = content.into_iter().unzip();
+        let image = HdrImage::new_with_content(
             Resolution {
-                width,
-                height
+                width: x1 - x0,
+                height: y1 - y0,
             },
-            content)
+            content);
+
+        if self.alpha {
+            image.with_alpha(coverage)
+        } else {
+            image
+        }
     }
-}
 
-fn ray_intensity(
-    hittable_list: &dyn Hittable,
-    background: &Intensity,
-    ray: &Ray,
-    accuracy: &Accuracy,
-    depth: usize,
-) -> Intensity {
-    if depth == 0 {
-        return Intensity::zero();
-    }
-
-    match hittable_list.hit(ray, accuracy) {
-        Some(hit_record) => match hit_record.material().scatter(&ray, &hit_record) {
-            ScatteringResult::ScatterredRay(attenuation, scattered) => {
-                let color =
-                    ray_intensity(hittable_list, background, &scattered, accuracy, depth - 1);
-                Intensity::new(
-                    attenuation.r() * color.r(),
-                    attenuation.g() * color.g(),
-                    attenuation.b() * color.b(),
-                )
+    fn build_integrator(&self, bvh: &BVHScene) -> Box<dyn Integrator> {
+        match self.integrator {
+            IntegratorKind::Path => Box::new(self.path_tracer.clone()),
+            IntegratorKind::AmbientOcclusion => {
+                let distance = self.ao_distance.unwrap_or_else(|| {
+                    bvh.bbox().map_or(f32::INFINITY, |bbox| 0.1 * bbox.extent().length()).max(f32::MIN_POSITIVE)
+                });
+                Box::new(AmbientOcclusion::new(distance))
             }
-            ScatteringResult::Light(rgb) => rgb,
-            ScatteringResult::None => Intensity::zero(),
-        },
-        None => *background,
+            IntegratorKind::Normals => Box::new(SurfaceView(SurfaceProperty::Normal)),
+            IntegratorKind::Depth => Box::new(SurfaceView(SurfaceProperty::Depth)),
+            IntegratorKind::Albedo => Box::new(SurfaceView(SurfaceProperty::Albedo)),
+            IntegratorKind::Uv => Box::new(SurfaceView(SurfaceProperty::Uv)),
+            IntegratorKind::BvhCost => Box::new(BvhCost),
+            IntegratorKind::MaterialId => Box::new(MaterialId),
+        }
     }
 }
 
-fn sample_time_point(frame: TimePoint, shutter: TimeInterval) -> TimePoint {
-    let TimePoint(tp) = frame;
-    let TimeInterval(interval) = shutter;
-    TimePoint(tp + random_from(-interval / 2., interval / 2.))
-}
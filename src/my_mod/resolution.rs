@@ -2,4 +2,13 @@
 pub struct Resolution {
     pub width: usize,
     pub height: usize
-}
\ No newline at end of file
+}
+
+/// A rectangle of pixels, `x0..x1` columns and `y0..y1` rows counted from the top-left corner.
+#[derive(Debug, Copy, Clone)]
+pub struct CropWindow {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
@@ -1,6 +1,7 @@
 use crate::my_mod::intensity::Intensity;
 
 #[derive(Debug, Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub struct RGB {
     r: f32,
     g: f32,
@@ -9,9 +10,9 @@ pub struct RGB {
 
 impl RGB {
     pub fn new(r: f32, g: f32, b: f32) -> RGB {
-        assert!(0. <= r && r <= 1.);
-        assert!(0. <= g && g <= 1.);
-        assert!(0. <= b && b <= 1.);
+        assert!((0. ..=1.).contains(&r));
+        assert!((0. ..=1.).contains(&g));
+        assert!((0. ..=1.).contains(&b));
         RGB { r, g, b }
     }
 
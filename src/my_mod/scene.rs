@@ -1,12 +1,101 @@
 use std::fmt::Debug;
+
+use glam::Vec3;
+
 use crate::my_mod::bvh::Boundable;
-use crate::my_mod::hittable::Hittable;
+use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
+use crate::my_mod::light::Light;
+use crate::my_mod::ray::Ray;
+use crate::my_mod::time::TimePoint;
+
+/// A point on a light source chosen for a shadow ray from some origin.
+///
+/// Its radiance depends on where and which side the light is hit, so it's read off the hit record
+/// of the light along `direction`.
+#[derive(Debug, Copy, Clone)]
+pub struct LightSample {
+    /// Unit vector from the origin to `point`.
+    pub direction: Vec3,
+    pub distance: f32,
+    /// Probability density of `direction` with respect to solid angle.
+    pub pdf: f32,
+}
+
+pub trait SceneObject : Boundable + Hittable + Send + Sync + Debug {
+    /// Unbounded objects are kept out of the BVH and tested against every ray.
+    fn is_bounded(&self) -> bool {
+        true
+    }
+
+    /// Whether the object emits light and supports `sample_towards`.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Picks a point on the object, as it is at `time`, visible from `origin` for next-event estimation.
+    fn sample_towards(&self, _origin: Vec3, _time: TimePoint) -> Option<LightSample> {
+        None
+    }
+
+    /// Whether `animate` moves the object.
+    fn is_animated(&self) -> bool {
+        false
+    }
+
+    /// Puts the object where it is at `frame` of an animation.
+    fn animate(&mut self, _frame: f32) {}
 
-pub trait SceneObject : Boundable + Hittable + Sync + Debug {
+    /// Like `hit`, but also counts the BVH nodes visited by hierarchies inside the object.
+    fn hit_counted(&self, ray: &Ray, range: &Accuracy) -> (Option<HitRecord<'_>>, usize) {
+        (self.hit(ray, range), 0)
+    }
+
+    /// Share of light passing through the object along the ray within `range`, for shadow rays.
+    /// Surfaces block it entirely, media may return an unbiased estimate.
+    fn transmittance(&self, ray: &Ray, range: &Accuracy) -> f32 {
+        if self.hit(ray, range).is_some() {
+            0.
+        } else {
+            1.
+        }
+    }
+
+    /// Solid angle density with which `sample_towards(origin, time)` returns `direction`.
+    fn pdf_towards(&self, _origin: Vec3, _direction: Vec3, _time: TimePoint) -> f32 {
+        0.
+    }
 }
 
 #[derive(Default)]
-pub struct Scene(pub Vec<Box<dyn SceneObject>>);
+pub struct Scene {
+    pub objects: Vec<Box<dyn SceneObject>>,
+    /// Lights without a surface, in addition to the emissive objects.
+    pub lights: Vec<Box<dyn Light>>,
+}
+
+impl Scene {
+    pub fn new(objects: Vec<Box<dyn SceneObject>>) -> Scene {
+        Scene { objects, lights: vec![] }
+    }
+
+    pub fn lights(mut self, lights: Vec<Box<dyn Light>>) -> Self {
+        self.lights = lights;
+        self
+    }
+
+    /// Whether `animate` moves anything.
+    pub fn is_animated(&self) -> bool {
+        self.objects.iter().any(|object| object.is_animated())
+    }
+
+    /// Puts the animated objects where they are at `frame`, a `TLAS` of the scene then needs a refit.
+    pub fn animate(&mut self, frame: f32) {
+        for object in &mut self.objects {
+            object.animate(frame);
+        }
+    }
+}
+
 //
 // impl Hittable for Scene {
 //     fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord> {
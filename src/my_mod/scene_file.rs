@@ -0,0 +1,1250 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use glam::{Affine3A, Mat3A, Mat4, Vec2, Vec3};
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::my_mod::angle::Angle;
+use crate::my_mod::blas::BLAS;
+use crate::my_mod::bvh::SplitStrategy;
+use crate::my_mod::camera::Camera;
+use crate::my_mod::cuboid::Cuboid;
+use crate::my_mod::disk::Disk;
+use crate::my_mod::emission::Emission;
+use crate::my_mod::environment::Environment;
+use crate::my_mod::grid::DensityGrid;
+use crate::my_mod::ies::IesProfile;
+use crate::my_mod::instance::Instance;
+use crate::my_mod::integrator::IntegratorKind;
+use crate::my_mod::intensity::Intensity;
+use crate::my_mod::light::{DirectionalLight, Light, PointLight, SpotLight};
+use crate::my_mod::material::{Attenuation, Fresnel, Material};
+use crate::my_mod::medium::{Atmosphere, ConstantMedium, GridMedium, PhaseFunction};
+use crate::my_mod::microfacet::{Ggx, Metal};
+use crate::my_mod::moving_sphere::MovingSphere;
+use crate::my_mod::obj::{self, ObjError};
+use crate::my_mod::plane::Plane;
+use crate::my_mod::principled::Principled;
+use crate::my_mod::quad::Quad;
+use crate::my_mod::renderer::Renderer;
+use crate::my_mod::resolution::Resolution;
+use crate::my_mod::scene::{Scene, SceneObject};
+use crate::my_mod::sky::Sky;
+use crate::my_mod::sphere::Sphere;
+use crate::my_mod::texture::{ImageTexture, Texture, WrapMode};
+use crate::my_mod::time::{Shutter, TimeInterval, TimePoint};
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(PathBuf, io::Error),
+    Syntax(PathBuf, serde_json::Error),
+    Invalid {
+        path: PathBuf,
+        field: String,
+        message: String,
+    },
+    Obj(ObjError),
+}
+
+impl Display for SceneFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneFileError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            SceneFileError::Syntax(path, error) => write!(f, "{}: {}", path.display(), error),
+            SceneFileError::Invalid { path, field, message } =>
+                write!(f, "{}: {}: {}", path.display(), field, message),
+            SceneFileError::Obj(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for SceneFileError {}
+
+type Vector = [f32; 3];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    camera: CameraDescription,
+    resolution: ResolutionDescription,
+    #[serde(default)]
+    renderer: RendererDescription,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialEntry>,
+    /// Objects shared by instances, not part of the scene themselves.
+    #[serde(default)]
+    prototypes: BTreeMap<String, ObjectDescription>,
+    objects: Vec<ObjectDescription>,
+    #[serde(default)]
+    lights: Vec<LightDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    from: Vector,
+    at: Vector,
+    #[serde(default = "default_up")]
+    up: Vector,
+    vfov: AngleDescription,
+    focus_dist: Option<f32>,
+    #[serde(default)]
+    aperture: f32,
+    shutter: Option<ShutterDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShutterDescription {
+    #[serde(default)]
+    frame: f32,
+    interval: f32,
+}
+
+fn default_up() -> Vector {
+    [0., 1., 0.]
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AngleDescription {
+    Degrees(f32),
+    Radians(f32),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ResolutionDescription {
+    width: usize,
+    height: usize,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RendererDescription {
+    samples_per_pixel: Option<usize>,
+    max_depth: Option<usize>,
+    max_diffuse_depth: Option<usize>,
+    max_specular_depth: Option<usize>,
+    max_transmission_depth: Option<usize>,
+    /// Bounces before Russian roulette may end a path.
+    roulette_depth: Option<usize>,
+    bvh: Option<String>,
+    light_sampling: Option<bool>,
+    integrator: Option<String>,
+    /// How far ambient occlusion looks for blockers.
+    ao_distance: Option<f32>,
+    atmosphere: Option<AtmosphereDescription>,
+    /// Radiance of rays leaving the scene, unless an `environment` is given.
+    background: Option<Vector>,
+    environment: Option<EnvironmentDescription>,
+    sky: Option<SkyDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDescription {
+    path: PathBuf,
+    rotation: Option<AngleDescription>,
+    #[serde(default = "default_environment_scale")]
+    scale: f32,
+}
+
+fn default_environment_scale() -> f32 {
+    1.
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SkyDescription {
+    elevation: AngleDescription,
+    azimuth: AngleDescription,
+    #[serde(default = "default_turbidity")]
+    turbidity: f32,
+    sun_radius: Option<AngleDescription>,
+    #[serde(default = "default_environment_scale")]
+    scale: f32,
+    /// Width of the baked environment map.
+    #[serde(default = "default_sky_resolution")]
+    resolution: usize,
+}
+
+fn default_turbidity() -> f32 {
+    3.
+}
+
+fn default_sky_resolution() -> usize {
+    1024
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AtmosphereDescription {
+    density: f32,
+    albedo: TextureDescription,
+    #[serde(default)]
+    phase: PhaseDescription,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum PhaseDescription {
+    #[default]
+    Isotropic,
+    HenyeyGreenstein(f32),
+}
+
+/// Any material may also give off light.
+#[derive(Deserialize)]
+struct MaterialEntry {
+    #[serde(flatten)]
+    material: MaterialDescription,
+    emission: Option<EmissionDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EmissionDescription {
+    color: TextureDescription,
+    #[serde(default = "default_emission_strength")]
+    strength: f32,
+    #[serde(default)]
+    two_sided: bool,
+    /// `strength` is the power of each object, spread over its area.
+    #[serde(default)]
+    normalize: bool,
+}
+
+fn default_emission_strength() -> f32 {
+    1.
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        albedo: TextureDescription,
+    },
+    Metal {
+        albedo: TextureDescription,
+        #[serde(default)]
+        fuzz: f32,
+    },
+    Dielectric {
+        refraction_index: f32,
+        fresnel: Option<String>,
+        absorption: Option<AbsorptionDescription>,
+        #[serde(default)]
+        thin: bool,
+        roughness: Option<RoughnessDescription>,
+    },
+    /// A `metal` preset or an explicit complex refractive index `eta + i k`.
+    Conductor {
+        metal: Option<String>,
+        eta: Option<Vector>,
+        k: Option<Vector>,
+        #[serde(default)]
+        roughness: RoughnessDescription,
+    },
+    Principled(Box<PrincipledDescription>),
+    Light {
+        emit: Vector,
+    },
+}
+
+/// Scalar parameters are gray textures, any left out get the defaults of `Principled::new`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrincipledDescription {
+    base_color: TextureDescription,
+    metallic: Option<TextureDescription>,
+    roughness: Option<TextureDescription>,
+    specular: Option<TextureDescription>,
+    sheen: Option<TextureDescription>,
+    sheen_tint: Option<TextureDescription>,
+    clearcoat: Option<TextureDescription>,
+    clearcoat_roughness: Option<TextureDescription>,
+    transmission: Option<TextureDescription>,
+    refraction_index: Option<f32>,
+}
+
+/// The same roughness in every direction or one along the tangent and one along the bitangent.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RoughnessDescription {
+    Isotropic(f32),
+    Anisotropic([f32; 2]),
+}
+
+impl Default for RoughnessDescription {
+    fn default() -> Self {
+        RoughnessDescription::Isotropic(0.)
+    }
+}
+
+/// The color white light takes on after travelling `distance` inside a dielectric.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AbsorptionDescription {
+    color: Vector,
+    #[serde(default = "default_absorption_distance")]
+    distance: f32,
+}
+
+fn default_absorption_distance() -> f32 {
+    1.
+}
+
+/// A constant gray value or color, or a pattern object.
+enum TextureDescription {
+    Color(Vector),
+    Pattern(PatternDescription),
+}
+
+// hand-written instead of `#[serde(untagged)]` to keep the position of errors in patterns
+impl<'de> Deserialize<'de> for TextureDescription {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TextureVisitor;
+
+        impl<'de> Visitor<'de> for TextureVisitor {
+            type Value = TextureDescription;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a gray value, an [r, g, b] color or a texture object")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Vector::deserialize(SeqAccessDeserializer::new(seq)).map(TextureDescription::Color)
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<Self::Value, E> {
+                Ok(TextureDescription::Color([value as f32; 3]))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                self.visit_f64(value as f64)
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                self.visit_f64(value as f64)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                PatternDescription::deserialize(MapAccessDeserializer::new(map)).map(TextureDescription::Pattern)
+            }
+        }
+
+        deserializer.deserialize_any(TextureVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum PatternDescription {
+    Checker {
+        even: Box<TextureDescription>,
+        odd: Box<TextureDescription>,
+        size: f32,
+    },
+    Noise {
+        scale: f32,
+    },
+    Turbulence {
+        scale: f32,
+        #[serde(default = "default_turbulence_depth")]
+        depth: usize,
+    },
+    Image {
+        path: PathBuf,
+        wrap: Option<String>,
+    },
+}
+
+fn default_turbulence_depth() -> usize {
+    7
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDescription {
+    Point {
+        position: Vector,
+        #[serde(default = "default_light_color")]
+        color: Vector,
+        intensity: f32,
+        profile: Option<PathBuf>,
+    },
+    Spot {
+        position: Vector,
+        direction: Vector,
+        #[serde(default = "default_light_color")]
+        color: Vector,
+        intensity: f32,
+        cone: AngleDescription,
+        falloff: Option<AngleDescription>,
+        profile: Option<PathBuf>,
+    },
+    Directional {
+        direction: Vector,
+        #[serde(default = "default_light_color")]
+        color: Vector,
+        irradiance: f32,
+    },
+}
+
+fn default_light_color() -> Vector {
+    [1., 1., 1.]
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDescription {
+    Sphere {
+        center: Vector,
+        radius: f32,
+        material: String,
+    },
+    Plane {
+        point: Vector,
+        normal: Vector,
+        material: String,
+    },
+    Quad {
+        corner: Vector,
+        u: Vector,
+        v: Vector,
+        material: String,
+    },
+    Disk {
+        center: Vector,
+        normal: Vector,
+        radius: f32,
+        material: String,
+    },
+    Cuboid {
+        min: Vector,
+        max: Vector,
+        material: String,
+    },
+    MovingSphere {
+        motion: MotionDescription,
+        radius: f32,
+        material: String,
+    },
+    Mesh {
+        path: PathBuf,
+    },
+    /// Objects sharing one BVH, built once.
+    Group {
+        objects: Vec<ObjectDescription>,
+    },
+    Instance {
+        prototype: String,
+        #[serde(default)]
+        transform: Vec<TransformDescription>,
+        /// Replaces `transform` with one interpolated between these when rendering several frames.
+        #[serde(default)]
+        keyframes: Vec<InstanceKeyframeDescription>,
+    },
+    /// Fog filling the boundary object, whose material isn't used.
+    Medium {
+        boundary: Box<ObjectDescription>,
+        density: f32,
+        albedo: TextureDescription,
+        #[serde(default)]
+        phase: PhaseDescription,
+    },
+    /// Smoke from a `.vol` density grid, placed by the box stored in the file.
+    GridMedium {
+        path: PathBuf,
+        #[serde(default = "default_density_scale")]
+        density: f32,
+        albedo: TextureDescription,
+        #[serde(default)]
+        phase: PhaseDescription,
+    },
+}
+
+fn default_density_scale() -> f32 {
+    1.
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDescription {
+    Translate(Vector),
+    Rotate {
+        axis: Vector,
+        angle: AngleDescription,
+    },
+    Scale(Vector),
+    /// Row-major, the last row must be `[0, 0, 0, 1]`.
+    Matrix([[f32; 4]; 4]),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum MotionDescription {
+    Linear {
+        from: Vector,
+        to: Vector,
+        #[serde(default)]
+        time0: f32,
+        #[serde(default = "default_time1")]
+        time1: f32,
+    },
+    Keyframes(Vec<KeyframeDescription>),
+}
+
+fn default_time1() -> f32 {
+    1.
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDescription {
+    time: f32,
+    center: Vector,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstanceKeyframeDescription {
+    frame: f32,
+    #[serde(default)]
+    transform: Vec<TransformDescription>,
+}
+
+/// Builds a `Scene` and a configured `Renderer` from a JSON scene description.
+///
+/// Relative mesh and texture paths are resolved against the directory of the scene file.
+pub fn load(path: impl AsRef<Path>) -> Result<(Scene, Renderer), SceneFileError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| SceneFileError::Io(path.to_path_buf(), e))?;
+    let description: SceneDescription = serde_json::from_str(&source)
+        .map_err(|e| SceneFileError::Syntax(path.to_path_buf(), e))?;
+
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let invalid = |field: String, message: String| SceneFileError::Invalid {
+        path: path.to_path_buf(),
+        field,
+        message,
+    };
+
+    let ResolutionDescription { width, height } = description.resolution;
+    if width < 2 || height < 2 {
+        return Err(invalid("resolution".to_string(), format!("{}x{} is too small", width, height)));
+    }
+    let resolution = Resolution { width, height };
+
+    let camera = {
+        let CameraDescription { from, at, up, vfov, focus_dist, aperture, shutter } = description.camera;
+        let from = Vec3::from(from);
+        let at = Vec3::from(at);
+        let up = Vec3::from(up);
+        if (from - at).length() == 0. {
+            return Err(invalid("camera.at".to_string(), "must differ from camera.from".to_string()));
+        }
+        if Vec3::cross(up, from - at).length() == 0. {
+            return Err(invalid("camera.up".to_string(), "must not be parallel to the view direction".to_string()));
+        }
+        let vfov = build_angle(vfov);
+        if !(0. < vfov.radians() && vfov.radians() < PI) {
+            return Err(invalid("camera.vfov".to_string(), "must be between 0 and 180 degrees".to_string()));
+        }
+        if aperture < 0. {
+            return Err(invalid("camera.aperture".to_string(), "must not be negative".to_string()));
+        }
+        let focus_dist = focus_dist.unwrap_or_else(|| (from - at).length());
+        if focus_dist <= 0. {
+            return Err(invalid("camera.focus_dist".to_string(), "must be positive".to_string()));
+        }
+        let aspect_ratio = width as f32 / height as f32;
+
+        let mut camera = Camera::new(&from, &at, &up, vfov, aspect_ratio, focus_dist, aperture);
+        if let Some(ShutterDescription { frame, interval }) = shutter {
+            if interval < 0. {
+                return Err(invalid("camera.shutter.interval".to_string(), "must not be negative".to_string()));
+            }
+            camera.set_shutter(Shutter::new(TimePoint(frame), TimeInterval(interval)));
+        }
+        camera
+    };
+
+    let shutter = camera.shutter();
+    let mut renderer = Renderer::new(camera, resolution);
+    if let Some(samples_per_pixel) = description.renderer.samples_per_pixel {
+        if samples_per_pixel == 0 {
+            return Err(invalid("renderer.samples_per_pixel".to_string(), "must be positive".to_string()));
+        }
+        renderer = renderer.samples_per_pixel(samples_per_pixel);
+    }
+    if let Some(max_depth) = description.renderer.max_depth {
+        renderer = renderer.max_depth(max_depth);
+    }
+    if let Some(max_depth) = description.renderer.max_diffuse_depth {
+        renderer = renderer.max_diffuse_depth(max_depth);
+    }
+    if let Some(max_depth) = description.renderer.max_specular_depth {
+        renderer = renderer.max_specular_depth(max_depth);
+    }
+    if let Some(max_depth) = description.renderer.max_transmission_depth {
+        renderer = renderer.max_transmission_depth(max_depth);
+    }
+    if let Some(roulette_depth) = description.renderer.roulette_depth {
+        renderer = renderer.roulette_depth(roulette_depth);
+    }
+    let mut strategy = SplitStrategy::default();
+    if let Some(bvh) = description.renderer.bvh {
+        strategy = bvh.parse::<SplitStrategy>()
+            .map_err(|message| invalid("renderer.bvh".to_string(), message))?;
+        renderer = renderer.split_strategy(strategy);
+    }
+    if let Some(light_sampling) = description.renderer.light_sampling {
+        renderer = renderer.light_sampling(light_sampling);
+    }
+    if let Some(integrator) = description.renderer.integrator {
+        let integrator = integrator.parse::<IntegratorKind>()
+            .map_err(|message| invalid("renderer.integrator".to_string(), message))?;
+        renderer = renderer.integrator(integrator);
+    }
+    if let Some(ao_distance) = description.renderer.ao_distance {
+        if ao_distance <= 0. {
+            return Err(invalid("renderer.ao_distance".to_string(), "must be positive".to_string()));
+        }
+        renderer = renderer.ao_distance(ao_distance);
+    }
+    if let Some(AtmosphereDescription { density, albedo, phase }) = description.renderer.atmosphere {
+        let field = |f: String| format!("renderer.atmosphere.{}", f);
+        if density <= 0. {
+            return Err(invalid(field("density".to_string()), "must be positive".to_string()));
+        }
+        let albedo = build_texture("albedo", albedo, directory).map_err(|(f, m)| invalid(field(f), m))?;
+        let phase = build_phase(phase).map_err(|(f, m)| invalid(field(f), m))?;
+        renderer = renderer.atmosphere(Atmosphere::new(density, albedo, phase));
+    }
+
+    if let Some([r, g, b]) = description.renderer.background {
+        if r < 0. || g < 0. || b < 0. {
+            return Err(invalid("renderer.background".to_string(), "components must not be negative".to_string()));
+        }
+        renderer = renderer.background(Intensity::new(r, g, b));
+    }
+    if description.renderer.environment.is_some() && description.renderer.sky.is_some() {
+        return Err(invalid("renderer.sky".to_string(), "can't be combined with an environment".to_string()));
+    }
+    if let Some(EnvironmentDescription { path: map, rotation, scale }) = description.renderer.environment {
+        if scale < 0. {
+            return Err(invalid("renderer.environment.scale".to_string(), "must not be negative".to_string()));
+        }
+        let environment = Environment::load(directory.join(map))
+            .map_err(|e| invalid("renderer.environment.path".to_string(), e.to_string()))?;
+        let rotation = rotation.map_or(Angle::Radians(0.), build_angle);
+        renderer = renderer.environment(environment.rotation(rotation).scale(scale));
+    }
+    if let Some(sky) = description.renderer.sky {
+        let SkyDescription { elevation, azimuth, turbidity, sun_radius, scale, resolution } = sky;
+        let field = |f: &str| format!("renderer.sky.{}", f);
+        let elevation = build_angle(elevation);
+        if !(0. ..=PI / 2.).contains(&elevation.radians()) {
+            return Err(invalid(field("elevation"), "must be within [0, 90] degrees".to_string()));
+        }
+        if !(1.7..=10.).contains(&turbidity) {
+            return Err(invalid(field("turbidity"), "must be within [1.7, 10]".to_string()));
+        }
+        if scale < 0. {
+            return Err(invalid(field("scale"), "must not be negative".to_string()));
+        }
+        if resolution < 4 {
+            return Err(invalid(field("resolution"), "must be at least 4".to_string()));
+        }
+        let mut sky = Sky::new(elevation, build_angle(azimuth), turbidity).scale(scale);
+        if let Some(radius) = sun_radius {
+            let radius = build_angle(radius);
+            if !(0. < radius.radians() && radius.radians() < PI / 4.) {
+                return Err(invalid(field("sun_radius"), "must be within (0, 45) degrees".to_string()));
+            }
+            sky = sky.sun_radius(radius);
+        }
+        renderer = renderer.environment(sky.environment(resolution));
+    }
+
+    let mut materials = BTreeMap::new();
+    for (name, MaterialEntry { material, emission }) in description.materials {
+        let field = format!("materials.{}", name);
+        let mut material = build_material(material, directory).map_err(|(f, m)| invalid(format!("{}.{}", field, f), m))?;
+        if let Some(emission) = emission {
+            let emission = build_emission(emission, directory)
+                .map_err(|(f, m)| invalid(format!("{}.emission.{}", field, f), m))?;
+            material = Material::emissive(material, emission);
+        }
+        materials.insert(name.clone(), material.named(&name));
+    }
+
+    let mut prototypes = BTreeMap::new();
+    for (name, prototype) in description.prototypes {
+        let field = format!("prototypes.{}", name);
+        let context = ObjectContext { materials: &materials, prototypes: None, directory, strategy, shutter };
+        let emits_power = emits_power(&prototype, &materials);
+        let object = build_object(prototype, &context).map_err(|e| e.within(&field).into_scene_file_error(path))?;
+        prototypes.insert(name, Prototype { object: Arc::from(object), emits_power });
+    }
+
+    let context = ObjectContext { materials: &materials, prototypes: Some(&prototypes), directory, strategy, shutter };
+    let mut objects = Vec::with_capacity(description.objects.len());
+    for (index, object) in description.objects.into_iter().enumerate() {
+        let field = format!("objects[{}]", index);
+        objects.push(build_object(object, &context).map_err(|e| e.within(&field).into_scene_file_error(path))?);
+    }
+    let mut lights = Vec::with_capacity(description.lights.len());
+    for (index, light) in description.lights.into_iter().enumerate() {
+        let light = build_light(light, directory)
+            .map_err(|(f, m)| invalid(format!("lights[{}].{}", index, f), m))?;
+        lights.push(light);
+    }
+    let scene = Scene::new(objects).lights(lights);
+
+    Ok((scene, renderer))
+}
+
+type FieldError = (String, String);
+
+fn build_attenuation(field: &str, [r, g, b]: Vector) -> Result<Attenuation, FieldError> {
+    if [r, g, b].iter().all(|c| (0. ..=1.).contains(c)) {
+        Ok(Attenuation::new(r, g, b))
+    } else {
+        Err((field.to_string(), "components must be within [0, 1]".to_string()))
+    }
+}
+
+fn build_texture(field: &str, description: TextureDescription, directory: &Path) -> Result<Texture, FieldError> {
+    let pattern = match description {
+        TextureDescription::Color(color) => return Ok(Texture::solid(build_attenuation(field, color)?)),
+        TextureDescription::Pattern(pattern) => pattern,
+    };
+
+    match pattern {
+        PatternDescription::Checker { even, odd, size } => {
+            if size <= 0. {
+                return Err((format!("{}.size", field), "must be positive".to_string()));
+            }
+            let even = build_texture(&format!("{}.even", field), *even, directory)?;
+            let odd = build_texture(&format!("{}.odd", field), *odd, directory)?;
+            Ok(Texture::checker(even, odd, size))
+        }
+        PatternDescription::Noise { scale } => Ok(Texture::noise(scale)),
+        PatternDescription::Turbulence { scale, depth } => Ok(Texture::turbulence(scale, depth)),
+        PatternDescription::Image { path, wrap } => {
+            let wrap = match wrap {
+                Some(wrap) => wrap.parse::<WrapMode>().map_err(|m| (format!("{}.wrap", field), m))?,
+                None => WrapMode::default(),
+            };
+            let image = ImageTexture::load(directory.join(path), wrap)
+                .map_err(|e| (format!("{}.path", field), e.to_string()))?;
+            Ok(Texture::image(image))
+        }
+    }
+}
+
+fn build_angle(description: AngleDescription) -> Angle {
+    match description {
+        AngleDescription::Degrees(val) => Angle::Degrees(val),
+        AngleDescription::Radians(val) => Angle::Radians(val),
+    }
+}
+
+fn build_emission(description: EmissionDescription, directory: &Path) -> Result<Emission, FieldError> {
+    let EmissionDescription { color, strength, two_sided, normalize } = description;
+    if strength < 0. {
+        return Err(("strength".to_string(), "must not be negative".to_string()));
+    }
+    let emission = Emission::new(build_texture("color", color, directory)?, strength).two_sided(two_sided);
+    Ok(if normalize { emission.power() } else { emission })
+}
+
+fn build_light(description: LightDescription, directory: &Path) -> Result<Box<dyn Light>, FieldError> {
+    let intensity = |field: &str, color: Vector, value: f32| {
+        if value < 0. {
+            return Err((field.to_string(), "must not be negative".to_string()));
+        }
+        Ok(value * Vec3::from(build_attenuation("color", color)?))
+    };
+    let direction = |direction: Vector| {
+        let direction = Vec3::from(direction);
+        if direction.length() == 0. {
+            return Err(("direction".to_string(), "must not be zero".to_string()));
+        }
+        Ok(direction)
+    };
+    let profile = |profile: PathBuf| {
+        IesProfile::load(directory.join(profile)).map_err(|e| ("profile".to_string(), e.to_string()))
+    };
+
+    match description {
+        LightDescription::Point { position, color, intensity: value, profile: path } => {
+            let mut light = PointLight::new(Vec3::from(position), intensity("intensity", color, value)?);
+            if let Some(path) = path {
+                light = light.profile(profile(path)?);
+            }
+            Ok(Box::new(light))
+        }
+        LightDescription::Spot { position, direction: axis, color, intensity: value, cone, falloff, profile: path } => {
+            let cone = build_angle(cone);
+            let cone_radians = cone.radians();
+            if !(0. < cone_radians && cone_radians < PI) {
+                return Err(("cone".to_string(), "must be within (0, 180) degrees".to_string()));
+            }
+            let mut light = SpotLight::new(Vec3::from(position), direction(axis)?, intensity("intensity", color, value)?, cone);
+            if let Some(falloff) = falloff {
+                let falloff = build_angle(falloff);
+                if !(0. ..=cone_radians).contains(&falloff.radians()) {
+                    return Err(("falloff".to_string(), "must be within [0, cone]".to_string()));
+                }
+                light = light.falloff(falloff);
+            }
+            if let Some(path) = path {
+                light = light.profile(profile(path)?);
+            }
+            Ok(Box::new(light))
+        }
+        LightDescription::Directional { direction: travel, color, irradiance } => {
+            Ok(Box::new(DirectionalLight::new(direction(travel)?, intensity("irradiance", color, irradiance)?)))
+        }
+    }
+}
+
+fn build_phase(description: PhaseDescription) -> Result<PhaseFunction, FieldError> {
+    match description {
+        PhaseDescription::Isotropic => Ok(PhaseFunction::Isotropic),
+        PhaseDescription::HenyeyGreenstein(g) => {
+            if !(-1. < g && g < 1.) {
+                return Err(("phase.henyey_greenstein".to_string(), "must be within (-1, 1)".to_string()));
+            }
+            Ok(PhaseFunction::henyey_greenstein(g))
+        }
+    }
+}
+
+fn build_roughness(description: RoughnessDescription) -> Result<Vec2, FieldError> {
+    let roughness = match description {
+        RoughnessDescription::Isotropic(roughness) => Vec2::splat(roughness),
+        RoughnessDescription::Anisotropic([u, v]) => Vec2::new(u, v),
+    };
+    if !((0. ..=1.).contains(&roughness.x) && (0. ..=1.).contains(&roughness.y)) {
+        return Err(("roughness".to_string(), "must be within [0, 1]".to_string()));
+    }
+    Ok(roughness)
+}
+
+fn build_material(description: MaterialDescription, directory: &Path) -> Result<Material, FieldError> {
+    match description {
+        MaterialDescription::Lambertian { albedo } =>
+            Ok(Material::lambertian(build_texture("albedo", albedo, directory)?)),
+        MaterialDescription::Metal { albedo, fuzz } => {
+            if fuzz < 0. {
+                return Err(("fuzz".to_string(), "must not be negative".to_string()));
+            }
+            Ok(Material::metal(build_texture("albedo", albedo, directory)?, fuzz))
+        }
+        MaterialDescription::Dielectric { refraction_index, fresnel, absorption, thin, roughness } => {
+            if refraction_index <= 0. {
+                return Err(("refraction_index".to_string(), "must be positive".to_string()));
+            }
+            let fresnel = match fresnel {
+                Some(fresnel) => fresnel.parse::<Fresnel>().map_err(|m| ("fresnel".to_string(), m))?,
+                None => Fresnel::default(),
+            };
+            let absorption = match absorption {
+                Some(_) if thin => {
+                    return Err(("absorption".to_string(), "thin-walled dielectrics don't absorb".to_string()));
+                }
+                Some(AbsorptionDescription { color, distance }) => {
+                    if !color.iter().all(|c| 0. < *c && *c <= 1.) {
+                        return Err(("absorption.color".to_string(), "components must be within (0, 1]".to_string()));
+                    }
+                    if distance <= 0. {
+                        return Err(("absorption.distance".to_string(), "must be positive".to_string()));
+                    }
+                    -Vec3::from(color.map(f32::ln)) / distance
+                }
+                None => Vec3::ZERO,
+            };
+            match roughness {
+                None => Ok(Material::Dielectric { refraction_index, fresnel, absorption, thin }),
+                Some(_) if thin => Err(("roughness".to_string(), "thin-walled dielectrics are smooth".to_string())),
+                Some(roughness) => {
+                    if !matches!(fresnel, Fresnel::Exact) {
+                        return Err(("fresnel".to_string(), "rough dielectrics use the exact Fresnel equations".to_string()));
+                    }
+                    let microfacet = Ggx::new(build_roughness(roughness)?);
+                    Ok(Material::RoughDielectric { refraction_index, absorption, microfacet })
+                }
+            }
+        }
+        MaterialDescription::Conductor { metal, eta, k, roughness } => {
+            let (eta, k) = match (metal, eta, k) {
+                (Some(metal), None, None) => metal.parse::<Metal>()
+                    .map_err(|m| ("metal".to_string(), m))?
+                    .refractive_index(),
+                (None, Some(eta), Some(k)) => {
+                    if eta.iter().any(|c| *c <= 0.) {
+                        return Err(("eta".to_string(), "components must be positive".to_string()));
+                    }
+                    if k.iter().any(|c| *c < 0.) {
+                        return Err(("k".to_string(), "components must not be negative".to_string()));
+                    }
+                    (eta.into(), k.into())
+                }
+                _ => return Err(("metal".to_string(), "expected either a metal or both eta and k".to_string())),
+            };
+            Ok(Material::conductor(eta, k, build_roughness(roughness)?))
+        }
+        MaterialDescription::Principled(principled) => {
+            let PrincipledDescription {
+                base_color,
+                metallic,
+                roughness,
+                specular,
+                sheen,
+                sheen_tint,
+                clearcoat,
+                clearcoat_roughness,
+                transmission,
+                refraction_index,
+            } = *principled;
+            let texture = |field: &str, description: Option<TextureDescription>| description
+                .map(|description| build_texture(field, description, directory))
+                .transpose();
+            let gray = |value: f32| Texture::solid(Attenuation::new(value, value, value));
+
+            let mut principled = Principled::new(build_texture("base_color", base_color, directory)?);
+            if let Some(metallic) = texture("metallic", metallic)? {
+                principled = principled.metallic(metallic);
+            }
+            if let Some(roughness) = texture("roughness", roughness)? {
+                principled = principled.roughness(roughness);
+            }
+            if let Some(specular) = texture("specular", specular)? {
+                principled = principled.specular(specular);
+            }
+            if let Some(sheen) = texture("sheen", sheen)? {
+                let tint = texture("sheen_tint", sheen_tint)?.unwrap_or_else(|| gray(0.5));
+                principled = principled.sheen(sheen, tint);
+            }
+            if let Some(clearcoat) = texture("clearcoat", clearcoat)? {
+                let roughness = texture("clearcoat_roughness", clearcoat_roughness)?.unwrap_or_else(|| gray(0.03));
+                principled = principled.clearcoat(clearcoat, roughness);
+            }
+            let refraction_index = refraction_index.unwrap_or(1.5);
+            if refraction_index <= 0. {
+                return Err(("refraction_index".to_string(), "must be positive".to_string()));
+            }
+            if let Some(transmission) = texture("transmission", transmission)? {
+                principled = principled.transmission(transmission, refraction_index);
+            }
+            Ok(Material::principled(principled))
+        }
+        MaterialDescription::Light { emit: [r, g, b] } => {
+            if r < 0. || g < 0. || b < 0. {
+                return Err(("emit".to_string(), "components must not be negative".to_string()));
+            }
+            Ok(Material::light(Intensity::new(r, g, b)))
+        }
+    }
+}
+
+/// Field errors are relative to the object being built, mesh errors carry their own location.
+enum ObjectError {
+    Field(FieldError),
+    Obj(ObjError),
+}
+
+impl ObjectError {
+    fn within(self, field: &str) -> ObjectError {
+        match self {
+            ObjectError::Field((f, m)) => ObjectError::Field((format!("{}.{}", field, f), m)),
+            error => error,
+        }
+    }
+
+    fn into_scene_file_error(self, path: &Path) -> SceneFileError {
+        match self {
+            ObjectError::Field((field, message)) => SceneFileError::Invalid { path: path.to_path_buf(), field, message },
+            ObjectError::Obj(error) => SceneFileError::Obj(error),
+        }
+    }
+}
+
+impl From<FieldError> for ObjectError {
+    fn from(error: FieldError) -> Self {
+        ObjectError::Field(error)
+    }
+}
+
+struct Prototype {
+    object: Arc<dyn SceneObject>,
+    /// Whether some emission is normalized to the power of an object, which only holds for the area
+    /// of the prototype, so instances must not stretch it.
+    emits_power: bool,
+}
+
+/// Whether a shape of the object emits a power spread over its area.
+fn emits_power(description: &ObjectDescription, materials: &BTreeMap<String, Material>) -> bool {
+    match description {
+        ObjectDescription::Sphere { material, .. }
+        | ObjectDescription::Plane { material, .. }
+        | ObjectDescription::Quad { material, .. }
+        | ObjectDescription::Disk { material, .. }
+        | ObjectDescription::Cuboid { material, .. }
+        | ObjectDescription::MovingSphere { material, .. } => materials.get(material).is_some_and(Material::emits_power),
+        ObjectDescription::Group { objects } => objects.iter().any(|object| emits_power(object, materials)),
+        ObjectDescription::Mesh { .. }
+        | ObjectDescription::Instance { .. }
+        | ObjectDescription::Medium { .. }
+        | ObjectDescription::GridMedium { .. } => false,
+    }
+}
+
+/// What objects may refer to while being built.
+struct ObjectContext<'a> {
+    materials: &'a BTreeMap<String, Material>,
+    /// `None` while the prototypes themselves are built.
+    prototypes: Option<&'a BTreeMap<String, Prototype>>,
+    directory: &'a Path,
+    /// Used for the BVHs of meshes and groups, which are built once while loading.
+    strategy: SplitStrategy,
+    shutter: Shutter,
+}
+
+fn build_object(description: ObjectDescription, context: &ObjectContext) -> Result<Box<dyn SceneObject>, ObjectError> {
+    match description {
+        ObjectDescription::Mesh { path } => {
+            let mesh = obj::load(context.directory.join(path)).map_err(ObjectError::Obj)?;
+            Ok(Box::new(BLAS::from_mesh(mesh, context.strategy)))
+        }
+        ObjectDescription::Group { objects } => {
+            let mut members = Vec::with_capacity(objects.len());
+            for (index, object) in objects.into_iter().enumerate() {
+                let field = format!("objects[{}]", index);
+                let member = build_object(object, context).map_err(|e| e.within(&field))?;
+                if !member.is_bounded() {
+                    return Err(ObjectError::Field((field, "groups can't hold unbounded objects".to_string())));
+                }
+                if member.is_animated() {
+                    return Err(ObjectError::Field((field, "groups can't hold animated instances".to_string())));
+                }
+                members.push(member);
+            }
+            Ok(Box::new(BLAS::new(members, context.strategy, context.shutter)))
+        }
+        ObjectDescription::Instance { prototype, transform, keyframes } => {
+            let prototypes = context.prototypes
+                .ok_or_else(|| ("prototype".to_string(), "prototypes can't contain instances".to_string()))?;
+            let Prototype { object, emits_power } = prototypes.get(&prototype)
+                .ok_or_else(|| ("prototype".to_string(), format!("unknown prototype '{}'", prototype)))?;
+            let check_rigid = |transform: &Affine3A| {
+                let linear = transform.matrix3;
+                if *emits_power && !(linear.transpose() * linear).abs_diff_eq(Mat3A::IDENTITY, 1e-4) {
+                    return Err((
+                        "transform".to_string(),
+                        format!("must only rotate, mirror and move prototype '{}', which emits a normalized power", prototype),
+                    ));
+                }
+                Ok(())
+            };
+            if keyframes.is_empty() {
+                let transform = build_transform(transform)?;
+                check_rigid(&transform)?;
+                return Ok(Box::new(Instance::new(object.clone(), transform)));
+            }
+            if !transform.is_empty() {
+                return Err(("transform".to_string(), "can't be given together with keyframes".to_string()).into());
+            }
+            let mut frames: Vec<(f32, Affine3A)> = Vec::with_capacity(keyframes.len());
+            for (index, InstanceKeyframeDescription { frame, transform }) in keyframes.into_iter().enumerate() {
+                let field = format!("keyframes[{}]", index);
+                let transform = build_transform(transform)
+                    .and_then(|transform| check_rigid(&transform).map(|_| transform))
+                    .map_err(|e| ObjectError::from(e).within(&field))?;
+                if let (Some(previous), Some(first)) = (frames.last(), frames.first()) {
+                    if frame <= previous.0 {
+                        return Err(ObjectError::Field((format!("{}.frame", field), "must come after the previous keyframe".to_string())));
+                    }
+                    // interpolating the scale between a mirrored and an unmirrored transform goes through zero
+                    if (transform.matrix3.determinant() < 0.) != (first.1.matrix3.determinant() < 0.) {
+                        return Err(ObjectError::Field((
+                            format!("{}.transform", field),
+                            "must mirror like the other keyframes".to_string(),
+                        )));
+                    }
+                }
+                frames.push((frame, transform));
+            }
+            Ok(Box::new(Instance::new(object.clone(), frames[0].1).keyframes(frames)))
+        }
+        ObjectDescription::Medium { boundary, density, albedo, phase } => {
+            if density <= 0. {
+                return Err(("density".to_string(), "must be positive".to_string()).into());
+            }
+            let albedo = build_texture("albedo", albedo, context.directory)?;
+            let phase = build_phase(phase)?;
+            let boundary = build_object(*boundary, context).map_err(|e| e.within("boundary"))?;
+            if boundary.is_animated() {
+                return Err(("boundary".to_string(), "can't be an animated instance".to_string()).into());
+            }
+            Ok(Box::new(ConstantMedium::new(boundary, density, albedo, phase)))
+        }
+        ObjectDescription::GridMedium { path, density, albedo, phase } => {
+            if density <= 0. {
+                return Err(("density".to_string(), "must be positive".to_string()).into());
+            }
+            let albedo = build_texture("albedo", albedo, context.directory)?;
+            let phase = build_phase(phase)?;
+            let grid = DensityGrid::load(context.directory.join(path))
+                .map_err(|e| ("path".to_string(), e.to_string()))?;
+            Ok(Box::new(GridMedium::new(grid, density, albedo, phase)))
+        }
+        shape => Ok(build_shape(shape, context.materials)?),
+    }
+}
+
+/// Builds the single primitives, the other objects are an error here and handled by `build_object`.
+fn build_shape(
+    description: ObjectDescription,
+    materials: &BTreeMap<String, Material>,
+) -> Result<Box<dyn SceneObject>, FieldError> {
+    // emission given as a power is spread over the surface of each shape
+    let material = |name: &String, area: Option<f32>| {
+        let material = materials.get(name).cloned().ok_or_else(|| (
+            "material".to_string(),
+            format!("unknown material '{}'", name),
+        ))?;
+        match area {
+            Some(area) => Ok(material.spread_over(area)),
+            None if material.emits_power() => Err((
+                "material".to_string(),
+                format!("material '{}' emits a normalized power, which needs a finite area", name),
+            )),
+            None => Ok(material),
+        }
+    };
+    match description {
+        ObjectDescription::Sphere { center, radius, material: name } => {
+            if radius <= 0. {
+                return Err(("radius".to_string(), "must be positive".to_string()));
+            }
+            Ok(Box::new(Sphere::new(center.into(), radius, material(&name, Some(4. * PI * radius * radius))?)))
+        }
+        ObjectDescription::Plane { point, normal, material: name } => {
+            if Vec3::from(normal).length() == 0. {
+                return Err(("normal".to_string(), "must not be zero".to_string()));
+            }
+            Ok(Box::new(Plane::new(point.into(), normal.into(), material(&name, None)?)))
+        }
+        ObjectDescription::Quad { corner, u, v, material: name } => {
+            let area = Vec3::cross(u.into(), v.into()).length();
+            if area == 0. {
+                return Err(("v".to_string(), "must not be parallel to u".to_string()));
+            }
+            Ok(Box::new(Quad::new(corner.into(), u.into(), v.into(), material(&name, Some(area))?)))
+        }
+        ObjectDescription::Disk { center, normal, radius, material: name } => {
+            if Vec3::from(normal).length() == 0. {
+                return Err(("normal".to_string(), "must not be zero".to_string()));
+            }
+            if radius <= 0. {
+                return Err(("radius".to_string(), "must be positive".to_string()));
+            }
+            Ok(Box::new(Disk::new(center.into(), normal.into(), radius, material(&name, Some(PI * radius * radius))?)))
+        }
+        ObjectDescription::Cuboid { min, max, material: name } => {
+            let (min, max) = (Vec3::from(min), Vec3::from(max));
+            if !min.cmplt(max).all() {
+                return Err(("max".to_string(), "must exceed min in every axis".to_string()));
+            }
+            let size = max - min;
+            let area = 2. * (size.x * size.y + size.y * size.z + size.z * size.x);
+            Ok(Box::new(Cuboid::new(min, max, material(&name, Some(area))?)))
+        }
+        ObjectDescription::MovingSphere { motion, radius, material: name } => {
+            if radius <= 0. {
+                return Err(("radius".to_string(), "must be positive".to_string()));
+            }
+            let material = material(&name, Some(4. * PI * radius * radius))?;
+            let sphere = match motion {
+                MotionDescription::Linear { from, to, time0, time1 } => {
+                    if time0 >= time1 {
+                        return Err(("motion.linear.time1".to_string(), "must follow time0".to_string()));
+                    }
+                    MovingSphere::linear(from.into(), TimePoint(time0), to.into(), TimePoint(time1), radius, material)
+                }
+                MotionDescription::Keyframes(keyframes) => {
+                    if keyframes.is_empty() {
+                        return Err(("motion.keyframes".to_string(), "must not be empty".to_string()));
+                    }
+                    if keyframes.windows(2).any(|pair| pair[0].time >= pair[1].time) {
+                        return Err((
+                            "motion.keyframes".to_string(),
+                            "times must be increasing".to_string(),
+                        ));
+                    }
+                    let keyframes = keyframes.into_iter()
+                        .map(|KeyframeDescription { time, center }| (TimePoint(time), center.into()))
+                        .collect();
+                    MovingSphere::keyframed(keyframes, radius, material)
+                }
+            };
+            Ok(Box::new(sphere))
+        }
+        ObjectDescription::Mesh { .. }
+        | ObjectDescription::Group { .. }
+        | ObjectDescription::Instance { .. }
+        | ObjectDescription::Medium { .. }
+        | ObjectDescription::GridMedium { .. } => Err(("type".to_string(), "is not a single shape".to_string())),
+    }
+}
+
+/// Composes the transforms, the first one in the list is applied first.
+fn build_transform(description: Vec<TransformDescription>) -> Result<Affine3A, FieldError> {
+    let mut transform = Affine3A::IDENTITY;
+    for (index, step) in description.into_iter().enumerate() {
+        let field = format!("transform[{}]", index);
+        let step = match step {
+            TransformDescription::Translate(offset) => Affine3A::from_translation(offset.into()),
+            TransformDescription::Rotate { axis, angle } => {
+                let axis = Vec3::from(axis);
+                if axis.length() == 0. {
+                    return Err((format!("{}.rotate.axis", field), "must not be zero".to_string()));
+                }
+                let angle = build_angle(angle);
+                Affine3A::from_axis_angle(axis.normalize(), angle.radians())
+            }
+            TransformDescription::Scale(factors) => {
+                if factors.contains(&0.) {
+                    return Err((format!("{}.scale", field), "factors must not be zero".to_string()));
+                }
+                Affine3A::from_scale(factors.into())
+            }
+            TransformDescription::Matrix(rows) => {
+                if rows[3] != [0., 0., 0., 1.] {
+                    return Err((format!("{}.matrix", field), "last row must be [0, 0, 0, 1]".to_string()));
+                }
+                Affine3A::from_mat4(Mat4::from_cols_array_2d(&rows).transpose())
+            }
+        };
+        transform = step * transform;
+    }
+
+    if transform.matrix3.determinant() == 0. {
+        return Err(("transform".to_string(), "must be invertible".to_string()));
+    }
+    Ok(transform)
+}
@@ -0,0 +1,170 @@
+use std::f32::consts::PI;
+
+use glam::Vec3;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::my_mod::angle::Angle;
+use crate::my_mod::environment::{direction_at, Environment};
+
+/// Angular radius of the sun seen from the earth.
+const SUN_RADIUS: f32 = 0.004_65;
+/// Luminance of the sun outside the atmosphere, in thousands of cd/m² like the sky.
+const SUN_LUMINANCE: f32 = 2.0e6;
+
+/// Clear sky after Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight" (1999),
+/// with the sun disk dimmed and reddened by the same atmosphere.
+///
+/// Radiance is in thousands of cd/m², a sunny scene lit by it is about 20 times brighter than one
+/// under the default white background. Below the horizon it's black, the ground is left to the scene.
+#[derive(Debug, Clone)]
+pub struct Sky {
+    /// Unit vector towards the sun.
+    sun: Vec3,
+    turbidity: f32,
+    sun_radius: f32,
+    scale: f32,
+}
+
+impl Sky {
+    /// The sun `elevation` above the horizon, within [0, 90] degrees, and its `azimuth` measured
+    /// from -z towards +x. `turbidity` is the haziness of the air, from 1.7 (clear) to 10 (hazy).
+    pub fn new(elevation: Angle, azimuth: Angle, turbidity: f32) -> Sky {
+        let (elevation, azimuth) = (elevation.radians(), azimuth.radians());
+        assert!((0. ..=PI / 2.).contains(&elevation), "sun elevation must be within [0, 90] degrees");
+        assert!((1.7..=10.).contains(&turbidity), "turbidity must be within [1.7, 10]");
+        Sky {
+            sun: Vec3::new(elevation.cos() * azimuth.sin(), elevation.sin(), -elevation.cos() * azimuth.cos()),
+            turbidity,
+            sun_radius: SUN_RADIUS,
+            scale: 1.,
+        }
+    }
+
+    pub fn sun_radius(mut self, radius: Angle) -> Self {
+        let radius = radius.radians();
+        assert!(0. < radius && radius < PI / 4., "sun radius must be within (0, 45) degrees");
+        self.sun_radius = radius;
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        assert!(scale >= 0., "sky scale must not be negative");
+        self.scale = scale;
+        self
+    }
+
+    /// Bakes the sky into an environment map `width` texels wide, which is sampled like any other.
+    /// Texels around the sun average the disk over their area, so it gives off the same light at any
+    /// resolution.
+    pub fn environment(&self, width: usize) -> Environment {
+        assert!(width >= 4, "sky needs a wider environment map");
+        let height = width / 2;
+        let sky = PerezSky::new(self.sun, self.turbidity);
+        let sun = sun_radiance(self.sun, self.turbidity);
+        // a texel spans at most this angle from its center
+        let texel = PI / height as f32 * 2f32.sqrt();
+        const SUBSAMPLES: usize = 8;
+
+        let texels = (0..height).into_par_iter().flat_map_iter(|row| (0..width).map(move |column| (column, row)))
+            .map(|(column, row)| {
+                let center = direction_at(width, height, column as f32 + 0.5, row as f32 + 0.5);
+                let mut radiance = sky.radiance(center);
+                if angle_between(center, self.sun) < self.sun_radius + texel {
+                    let covered = (0..SUBSAMPLES * SUBSAMPLES)
+                        .filter(|i| {
+                            let x = column as f32 + (i % SUBSAMPLES) as f32 / SUBSAMPLES as f32;
+                            let y = row as f32 + (i / SUBSAMPLES) as f32 / SUBSAMPLES as f32;
+                            let offset = 0.5 / SUBSAMPLES as f32;
+                            angle_between(direction_at(width, height, x + offset, y + offset), self.sun) < self.sun_radius
+                        })
+                        .count();
+                    radiance += sun * covered as f32 / (SUBSAMPLES * SUBSAMPLES) as f32;
+                }
+                self.scale * radiance
+            })
+            .collect();
+        Environment::new(width, height, texels)
+    }
+}
+
+fn angle_between(a: Vec3, b: Vec3) -> f32 {
+    Vec3::dot(a, b).clamp(-1., 1.).acos()
+}
+
+/// The sky dome of the Preetham model: luminance and chromaticity follow Perez distributions
+/// anchored at the zenith.
+struct PerezSky {
+    sun: Vec3,
+    /// Zenith values of the luminance `Y` and the chromaticities `x` and `y`.
+    zenith: Vec3,
+    /// Distribution coefficients `A` to `E` of `Y`, `x` and `y`.
+    coefficients: [[f32; 5]; 3],
+}
+
+impl PerezSky {
+    fn new(sun: Vec3, turbidity: f32) -> PerezSky {
+        let t = turbidity;
+        let theta = sun.y.clamp(-1., 1.).acos();
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let cubic = |c: [f32; 4]| c[0] * theta.powi(3) + c[1] * theta.powi(2) + c[2] * theta + c[3];
+        let x = t * t * cubic([0.00166, -0.00375, 0.00209, 0.])
+            + t * cubic([-0.02903, 0.06377, -0.03202, 0.00394])
+            + cubic([0.11693, -0.21196, 0.06052, 0.25886]);
+        let y = t * t * cubic([0.00275, -0.00610, 0.00317, 0.])
+            + t * cubic([-0.04214, 0.08970, -0.04153, 0.00516])
+            + cubic([0.15346, -0.26756, 0.06670, 0.26688]);
+
+        let coefficients = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+        PerezSky { sun, zenith: Vec3::new(luminance.max(0.), x, y), coefficients }
+    }
+
+    /// Linear sRGB radiance along the unit vector `direction`.
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        if direction.y <= 0. {
+            return Vec3::ZERO;
+        }
+        let cos_theta = direction.y.max(1e-3);
+        let gamma = angle_between(direction, self.sun);
+        let theta_sun = self.sun.y.clamp(-1., 1.).acos();
+
+        let perez = |[a, b, c, d, e]: [f32; 5], cos_theta: f32, gamma: f32| {
+            (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+        };
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            let coefficients = self.coefficients[i];
+            self.zenith[i] * perez(coefficients, cos_theta, gamma) / perez(coefficients, 1., theta_sun)
+        });
+        xyy_to_rgb(x, y, luminance)
+    }
+}
+
+/// Linear sRGB of the CIE chromaticity `(x, y)` with luminance `luminance`.
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Vec3 {
+    if y <= 0. {
+        return Vec3::ZERO;
+    }
+    let (big_x, big_y, big_z) = (x / y * luminance, luminance, (1. - x - y) / y * luminance);
+    Vec3::new(
+        3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z,
+    ).max(Vec3::ZERO)
+}
+
+/// Radiance of the sun disk after passing the atmosphere: Rayleigh scattering and Ångström's aerosol
+/// extinction at red, green and blue wavelengths, over the air mass of Kasten and Young.
+fn sun_radiance(sun: Vec3, turbidity: f32) -> Vec3 {
+    let zenith = sun.y.clamp(0., 1.).acos().to_degrees();
+    let air_mass = 1. / (sun.y.max(0.) + 0.50572 * (96.07995 - zenith).powf(-1.6364));
+    let beta = 0.04608 * turbidity - 0.04586;
+    // wavelengths in micrometres
+    let depth = |wavelength: f32| 0.008735 * wavelength.powf(-4.08) + beta * wavelength.powf(-1.3);
+    let transmittance = |wavelength: f32| (-air_mass * depth(wavelength)).exp();
+    SUN_LUMINANCE * Vec3::new(transmittance(0.68), transmittance(0.55), transmittance(0.44))
+}
@@ -1,11 +1,16 @@
-use glam::Vec3;
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3};
 
 use crate::my_mod::bbox::BBox;
 use crate::my_mod::bvh::Boundable;
 use crate::my_mod::hittable::{Accuracy, HitRecord, Hittable};
 use crate::my_mod::material::Material;
 use crate::my_mod::ray::Ray;
-use crate::my_mod::scene::SceneObject;
+use crate::my_mod::scene::{LightSample, SceneObject};
+use crate::my_mod::time::{Shutter, TimePoint};
+use crate::my_mod::utils::{random, random_on_unit_sphere};
+use crate::my_mod::vec3;
 
 #[derive(Debug)]
 pub struct Sphere {
@@ -33,8 +38,15 @@ impl Sphere {
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord> {
-        let oc = ray.origin() - self.center();
+    fn hit(&self, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        self.hit_at(self.center(), ray, accuracy)
+    }
+}
+
+impl Sphere {
+    /// Intersects the sphere moved to `center`, shared with `MovingSphere`.
+    pub(crate) fn hit_at(&self, center: Vec3, ray: &Ray, accuracy: &Accuracy) -> Option<HitRecord<'_>> {
+        let oc = ray.origin() - center;
         let a = Vec3::dot(ray.direction(), ray.direction());
         let b = 2. * Vec3::dot(oc, ray.direction());
         let c = Vec3::dot(oc, oc) - self.radius().powi(2);
@@ -63,7 +75,7 @@ impl Hittable for Sphere {
         };
 
         let point = ray.at(t);
-        let outward_normal = (ray.at(t) - self.center()).normalize();
+        let outward_normal = (ray.at(t) - center).normalize();
         let front = Vec3::dot(ray.direction(), outward_normal) < 0.;
         let normal = if front {
             outward_normal
@@ -71,20 +83,120 @@ impl Hittable for Sphere {
             -outward_normal
         };
 
-        let hit_record = HitRecord::new(point, normal, &self.material, t, front);
+        // longitude from -X around +Y, latitude from the bottom pole
+        let uv = Vec2::new(
+            (f32::atan2(-outward_normal.z, outward_normal.x) + PI) / (2. * PI),
+            f32::acos(-outward_normal.y.clamp(-1., 1.)) / PI,
+        );
+
+        let dpdu = Vec3::new(outward_normal.z, 0., -outward_normal.x);
+        let hit_record = HitRecord::new(point, normal, uv, &self.material, t, front).dpdu(dpdu);
 
         Some(hit_record)
     }
 }
 
 impl Boundable for Sphere {
-    fn bbox(&self) -> BBox {
+    fn bbox(&self, _shutter: &Shutter) -> BBox {
+        self.bbox_at(self.center)
+    }
+}
+
+impl Sphere {
+    pub(crate) fn bbox_at(&self, center: Vec3) -> BBox {
         BBox {
-            min: self.center - Vec3::new(self.radius, self.radius, self.radius),
-            max: self.center + Vec3::new(self.radius, self.radius, self.radius)
+            min: center - Vec3::new(self.radius, self.radius, self.radius),
+            max: center + Vec3::new(self.radius, self.radius, self.radius)
         }
     }
+
+    /// `1 - cos` of the half-angle of the cone the sphere fills when seen from `distance_squared` away.
+    fn cone_one_minus_cos(&self, distance_squared: f32) -> f32 {
+        let sin_squared = self.radius.powi(2) / distance_squared;
+        let cos_max = (1. - sin_squared).max(0.).sqrt();
+        sin_squared / (1. + cos_max)
+    }
+
+    fn area_pdf(&self, center: Vec3, direction: Vec3, point: Vec3, distance: f32) -> f32 {
+        let normal = (point - center) / self.radius;
+        let cos = Vec3::dot(normal, direction).abs().max(1e-6);
+        distance.powi(2) / (cos * 4. * PI * self.radius.powi(2))
+    }
 }
 
 impl SceneObject for Sphere {
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_towards(&self, origin: Vec3, _time: TimePoint) -> Option<LightSample> {
+        self.sample_towards_at(self.center, origin)
+    }
+
+    fn pdf_towards(&self, origin: Vec3, direction: Vec3, _time: TimePoint) -> f32 {
+        self.pdf_towards_at(self.center, origin, direction)
+    }
+}
+
+impl Sphere {
+    pub(crate) fn sample_towards_at(&self, center: Vec3, origin: Vec3) -> Option<LightSample> {
+        let oc = origin - center;
+        let distance_squared = oc.length_squared();
+
+        let (direction, distance, pdf) = if distance_squared > self.radius.powi(2) {
+            // uniform in the cone of directions towards the sphere
+            let one_minus_cos = self.cone_one_minus_cos(distance_squared);
+            let cos = 1. - random::<f32>() * one_minus_cos;
+            let sin = (1. - cos * cos).max(0.).sqrt();
+            let phi = 2. * PI * random::<f32>();
+
+            let w = -oc / distance_squared.sqrt();
+            let (a, b) = vec3::orthonormal_basis(w);
+            let direction = (a * phi.cos() * sin + b * phi.sin() * sin + w * cos).normalize();
+
+            let half_b = Vec3::dot(oc, direction);
+            let discriminant = (half_b * half_b - distance_squared + self.radius.powi(2)).max(0.);
+            let distance = -half_b - discriminant.sqrt();
+
+            (direction, distance, 1. / (2. * PI * one_minus_cos))
+        } else {
+            // inside every point is visible, sample the area uniformly
+            let point = center + self.radius * random_on_unit_sphere();
+            let to_point = point - origin;
+            let distance = to_point.length();
+            if distance == 0. {
+                return None;
+            }
+            let direction = to_point / distance;
+
+            (direction, distance, self.area_pdf(center, direction, point, distance))
+        };
+
+        Some(LightSample {
+            direction,
+            distance,
+            pdf,
+        })
+    }
+
+    pub(crate) fn pdf_towards_at(&self, center: Vec3, origin: Vec3, direction: Vec3) -> f32 {
+        let oc = origin - center;
+        let distance_squared = oc.length_squared();
+
+        let half_b = Vec3::dot(oc, direction);
+        let discriminant = half_b * half_b - distance_squared + self.radius.powi(2);
+        if discriminant < 0. {
+            return 0.;
+        }
+
+        if distance_squared > self.radius.powi(2) {
+            if -half_b - discriminant.sqrt() < 0. {
+                return 0.;
+            }
+            1. / (2. * PI * self.cone_one_minus_cos(distance_squared))
+        } else {
+            let distance = -half_b + discriminant.sqrt();
+            self.area_pdf(center, direction, origin + distance * direction, distance)
+        }
+    }
 }
\ No newline at end of file
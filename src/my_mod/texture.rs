@@ -0,0 +1,326 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3};
+
+use crate::my_mod::material::Attenuation;
+use crate::my_mod::perlin::Perlin;
+use crate::my_mod::tonemap::srgb_to_linear;
+
+/// Spatially varying reflectance sampled by materials at a hit point.
+#[derive(Debug, Clone)]
+pub enum Texture {
+    Solid(Attenuation),
+    /// 3D checkerboard of cubes with edge `size` in world space.
+    Checker {
+        even: Box<Texture>,
+        odd: Box<Texture>,
+        size: f32,
+    },
+    Noise {
+        perlin: Arc<Perlin>,
+        scale: f32,
+    },
+    Turbulence {
+        perlin: Arc<Perlin>,
+        scale: f32,
+        depth: usize,
+    },
+    Image(Arc<ImageTexture>),
+}
+
+impl Texture {
+    pub fn solid(color: Attenuation) -> Texture {
+        Texture::Solid(color)
+    }
+
+    pub fn checker(even: Texture, odd: Texture, size: f32) -> Texture {
+        assert!(size > 0.);
+        Texture::Checker {
+            even: Box::new(even),
+            odd: Box::new(odd),
+            size,
+        }
+    }
+
+    pub fn noise(scale: f32) -> Texture {
+        Texture::Noise {
+            perlin: Arc::new(Perlin::new()),
+            scale,
+        }
+    }
+
+    pub fn turbulence(scale: f32, depth: usize) -> Texture {
+        Texture::Turbulence {
+            perlin: Arc::new(Perlin::new()),
+            scale,
+            depth,
+        }
+    }
+
+    pub fn image(image: ImageTexture) -> Texture {
+        Texture::Image(Arc::new(image))
+    }
+
+    pub fn value(&self, uv: Vec2, point: Vec3) -> Attenuation {
+        match self {
+            Texture::Solid(color) => *color,
+            Texture::Checker { even, odd, size } => {
+                let cell = (point / *size).floor();
+                if (cell.x + cell.y + cell.z) as i64 % 2 == 0 {
+                    even.value(uv, point)
+                } else {
+                    odd.value(uv, point)
+                }
+            }
+            Texture::Noise { perlin, scale } => {
+                let gray = (0.5 * (1. + perlin.noise(*scale * point))).clamp(0., 1.);
+                Attenuation::new(gray, gray, gray)
+            }
+            Texture::Turbulence { perlin, scale, depth } => {
+                let gray = perlin.turbulence(*scale * point, *depth).clamp(0., 1.);
+                Attenuation::new(gray, gray, gray)
+            }
+            Texture::Image(image) => {
+                let color = image.sample(uv);
+                Attenuation::new(color.x, color.y, color.z)
+            }
+        }
+    }
+}
+
+impl From<Attenuation> for Texture {
+    fn from(color: Attenuation) -> Self {
+        Texture::Solid(color)
+    }
+}
+
+/// What lookups outside of `[0, 1]` UV coordinates return.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    fn apply(&self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let wrapped = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n { i } else { 2 * n - 1 - i }
+            }
+        };
+        wrapped as usize
+    }
+}
+
+impl FromStr for WrapMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "repeat" => Ok(WrapMode::Repeat),
+            "clamp" => Ok(WrapMode::Clamp),
+            "mirror" => Ok(WrapMode::Mirror),
+            _ => Err(format!("unknown wrap mode '{}', expected repeat, clamp or mirror", s)),
+        }
+    }
+}
+
+/// Bilinearly filtered image, texels are stored as linear RGB in `[0, 1]`.
+#[derive(Debug)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3>,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, texels: Vec<Vec3>, wrap: WrapMode) -> Self {
+        assert!(width > 0 && height > 0);
+        assert_eq!(width * height, texels.len());
+        Self {
+            width,
+            height,
+            texels,
+            wrap,
+        }
+    }
+
+    /// Loads an sRGB encoded PNG or PPM (P3/P6) file.
+    pub fn load(path: impl AsRef<Path>, wrap: WrapMode) -> io::Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let (width, height, encoded) = match extension.as_deref() {
+            Some("png") => read_png(path)?,
+            Some("ppm") | Some("pnm") => read_ppm(path)?,
+            _ => return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: unsupported texture format, expected .png or .ppm", path.display()),
+            )),
+        };
+        let texels = encoded.into_iter()
+            .map(|c| Vec3::new(srgb_to_linear(c.x), srgb_to_linear(c.y), srgb_to_linear(c.z)))
+            .collect();
+        Ok(Self::new(width, height, texels, wrap))
+    }
+
+    /// Looks up the image with `v` growing upwards, `(0, 0)` is the bottom-left corner.
+    pub fn sample(&self, uv: Vec2) -> Vec3 {
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = (1. - uv.y) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let texel = |x: i64, y: i64| {
+            let x = self.wrap.apply(x, self.width);
+            let y = self.wrap.apply(y, self.height);
+            self.texels[y * self.width + x]
+        };
+
+        let top = texel(x0, y0) * (1. - fx) + texel(x0 + 1, y0) * fx;
+        let bottom = texel(x0, y0 + 1) * (1. - fx) + texel(x0 + 1, y0 + 1) * fx;
+        (top * (1. - fy) + bottom * fy).clamp(Vec3::ZERO, Vec3::ONE)
+    }
+}
+
+fn invalid_data(path: &Path, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message))
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+fn read_png(path: &Path) -> io::Result<(usize, usize, Vec<Vec3>)> {
+    let mut decoder = png::Decoder::new(BufReader::new(open(path)?));
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+
+    let channels = info.color_type.samples();
+    let (bytes_per_sample, max) = match info.bit_depth {
+        png::BitDepth::Sixteen => (2, u16::MAX as f32),
+        _ => (1, u8::MAX as f32),
+    };
+    let sample = |i: usize| -> f32 {
+        let value = if bytes_per_sample == 2 {
+            u16::from_be_bytes([buffer[2 * i], buffer[2 * i + 1]]) as f32
+        } else {
+            buffer[i] as f32
+        };
+        value / max
+    };
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let texels = (0..width * height)
+        .map(|pixel| {
+            let first = pixel * channels;
+            match channels {
+                // grayscale, optionally with alpha
+                1 | 2 => Vec3::splat(sample(first)),
+                _ => Vec3::new(sample(first), sample(first + 1), sample(first + 2)),
+            }
+        })
+        .collect();
+
+    Ok((width, height, texels))
+}
+
+struct PpmHeaderReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl PpmHeaderReader<'_> {
+    /// Header tokens are separated by whitespace, comments run to the end of the line.
+    fn next_token(&mut self) -> Option<&str> {
+        let data = self.data;
+        loop {
+            while self.position < data.len() && data[self.position].is_ascii_whitespace() {
+                self.position += 1;
+            }
+            if self.position < data.len() && data[self.position] == b'#' {
+                while self.position < data.len() && data[self.position] != b'\n' {
+                    self.position += 1;
+                }
+            } else {
+                break;
+            }
+        }
+        let start = self.position;
+        while self.position < data.len() && !data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        if start == self.position {
+            None
+        } else {
+            std::str::from_utf8(&data[start..self.position]).ok()
+        }
+    }
+
+    fn next_number(&mut self) -> Option<usize> {
+        self.next_token()?.parse().ok()
+    }
+}
+
+fn read_ppm(path: &Path) -> io::Result<(usize, usize, Vec<Vec3>)> {
+    let mut data = vec![];
+    open(path)?.read_to_end(&mut data)?;
+
+    let mut reader = PpmHeaderReader { data: &data, position: 0 };
+    let binary = match reader.next_token() {
+        Some("P3") => false,
+        Some("P6") => true,
+        _ => return Err(invalid_data(path, "expected a P3 or P6 PPM file")),
+    };
+    let (width, height, max) = match (reader.next_number(), reader.next_number(), reader.next_number()) {
+        (Some(width), Some(height), Some(max)) if width > 0 && height > 0 && 0 < max && max <= 65535 =>
+            (width, height, max as f32),
+        _ => return Err(invalid_data(path, "invalid header")),
+    };
+
+    let count = width * height * 3;
+    let samples: Vec<f32> = if binary {
+        // a single whitespace separates the header from the raster
+        let start = reader.position + 1;
+        let bytes_per_sample = if max < 256. { 1 } else { 2 };
+        let raster = data.get(start..start + count * bytes_per_sample)
+            .ok_or_else(|| invalid_data(path, "truncated raster"))?;
+        (0..count)
+            .map(|i| {
+                let value = if bytes_per_sample == 2 {
+                    u16::from_be_bytes([raster[2 * i], raster[2 * i + 1]]) as f32
+                } else {
+                    raster[i] as f32
+                };
+                value / max
+            })
+            .collect()
+    } else {
+        (0..count)
+            .map(|_| reader.next_number()
+                .map(|value| value as f32 / max)
+                .ok_or_else(|| invalid_data(path, "truncated raster")))
+            .collect::<io::Result<_>>()?
+    };
+
+    let texels = samples.chunks(3)
+        .map(|c| Vec3::new(c[0], c[1], c[2]).clamp(Vec3::ZERO, Vec3::ONE))
+        .collect();
+
+    Ok((width, height, texels))
+}
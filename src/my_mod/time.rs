@@ -1,5 +1,39 @@
-#[derive(Copy, Clone)]
+use crate::my_mod::utils::random_from;
+
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
 pub struct TimePoint(pub f32);
 
-#[derive(Debug, Copy, Clone)]
-pub struct TimeInterval(pub f32);
\ No newline at end of file
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+pub struct TimeInterval(pub f32);
+
+/// The span of time during which the camera gathers light, centered on `frame`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Shutter {
+    pub frame: TimePoint,
+    pub interval: TimeInterval,
+}
+
+impl Shutter {
+    pub fn new(frame: TimePoint, interval: TimeInterval) -> Self {
+        assert!(interval.0 >= 0.);
+        Self { frame, interval }
+    }
+
+    pub fn open(&self) -> TimePoint {
+        TimePoint(self.frame.0 - self.interval.0 / 2.)
+    }
+
+    pub fn close(&self) -> TimePoint {
+        TimePoint(self.frame.0 + self.interval.0 / 2.)
+    }
+
+    /// A uniformly distributed moment while the shutter is open.
+    pub fn sample(&self) -> TimePoint {
+        let TimePoint(tp) = self.frame;
+        let TimeInterval(interval) = self.interval;
+        if interval == 0. {
+            return self.frame;
+        }
+        TimePoint(tp + random_from(-interval / 2., interval / 2.))
+    }
+}
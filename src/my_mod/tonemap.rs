@@ -0,0 +1,143 @@
+use std::str::FromStr;
+
+use glam::Vec3;
+
+use crate::my_mod::rgb::RGB;
+
+/// Compresses linear radiance into `[0, 1]` before encoding it for display.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ToneMap {
+    /// Clips everything above 1.
+    #[default]
+    Clamp,
+    /// `L / (1 + L)` on the luminance, never reaches white.
+    Reinhard,
+    /// Reinhard reaching white at the white point.
+    ExtendedReinhard,
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    /// Uncharted 2 filmic curve by John Hable, normalized to the white point.
+    Hable,
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(ToneMap::Clamp),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            "reinhard-extended" => Ok(ToneMap::ExtendedReinhard),
+            "aces" => Ok(ToneMap::Aces),
+            "hable" => Ok(ToneMap::Hable),
+            _ => Err(format!(
+                "unknown tone mapping '{}', expected clamp, reinhard, reinhard-extended, aces or hable", s,
+            )),
+        }
+    }
+}
+
+/// Converts scene radiance into sRGB display values: exposure, tone mapping and the sRGB transfer function.
+#[derive(Debug, Copy, Clone)]
+pub struct DisplayTransform {
+    tone_map: ToneMap,
+    exposure: f32,
+    white_point: f32,
+}
+
+impl DisplayTransform {
+    pub fn new(tone_map: ToneMap) -> Self {
+        Self {
+            tone_map,
+            exposure: 0.,
+            white_point: 11.2,
+        }
+    }
+
+    /// Scales the radiance by `2^stops` before tone mapping.
+    pub fn exposure(mut self, stops: f32) -> Self {
+        self.exposure = stops;
+        self
+    }
+
+    /// The exposed radiance mapped to white by the extended Reinhard and Hable curves.
+    pub fn white_point(mut self, white_point: f32) -> Self {
+        assert!(white_point > 0.);
+        self.white_point = white_point;
+        self
+    }
+
+    pub fn apply(&self, radiance: Vec3) -> RGB {
+        // NaN and negative samples would break the curves
+        let radiance = Vec3::select(radiance.cmpge(Vec3::ZERO), radiance, Vec3::ZERO);
+        let exposed = radiance * 2f32.powf(self.exposure);
+
+        let mapped = match self.tone_map {
+            ToneMap::Clamp => exposed,
+            ToneMap::Reinhard => scale_luminance(exposed, |l| l / (1. + l)),
+            ToneMap::ExtendedReinhard => {
+                let white = self.white_point * self.white_point;
+                scale_luminance(exposed, |l| l * (1. + l / white) / (1. + l))
+            }
+            ToneMap::Aces => {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                (exposed * (a * exposed + b)) / (exposed * (c * exposed + d) + e)
+            }
+            ToneMap::Hable => {
+                let white = hable(Vec3::splat(self.white_point));
+                hable(exposed) / white
+            }
+        };
+
+        let mapped = mapped.clamp(Vec3::ZERO, Vec3::ONE);
+        RGB::new(
+            linear_to_srgb(mapped.x),
+            linear_to_srgb(mapped.y),
+            linear_to_srgb(mapped.z),
+        )
+    }
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self::new(ToneMap::default())
+    }
+}
+
+/// Rec. 709 relative luminance of linear RGB.
+fn luminance(color: Vec3) -> f32 {
+    Vec3::dot(color, Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+/// Maps the luminance and scales the color along, keeping its hue.
+fn scale_luminance(color: Vec3, curve: impl Fn(f32) -> f32) -> Vec3 {
+    let l = luminance(color);
+    if l > 0. {
+        color * (curve(l) / l)
+    } else {
+        color
+    }
+}
+
+fn hable(x: Vec3) -> Vec3 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+/// The piecewise sRGB opto-electronic transfer function.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `linear_to_srgb`.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -1,8 +1,14 @@
+use std::cell::RefCell;
+use std::f32::consts::PI;
+
 use glam::Vec3;
-use rand::random;
+use rand::distributions::{Distribution, Standard};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
 
 use crate::my_mod::{image::Image, rgb::RGB};
 use crate::my_mod::intensity::Intensity;
+use crate::my_mod::material::Attenuation;
 use crate::my_mod::resolution::Resolution;
 
 impl From<Intensity> for Vec3 {
@@ -11,22 +17,42 @@ impl From<Intensity> for Vec3 {
     }
 }
 
+impl From<Attenuation> for Vec3 {
+    fn from(attenuation: Attenuation) -> Self {
+        Vec3::new(attenuation.r(), attenuation.g(), attenuation.b())
+    }
+}
+
 impl From<RGB> for Vec3 {
     fn from(rgb: RGB) -> Self {
         Vec3::new(rgb.r(), rgb.g(), rgb.b())
     }
 }
 
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// Like `rand::random`, but draws from a per-thread generator that can be reseeded.
+pub fn random<T>() -> T where Standard: Distribution<T> {
+    RNG.with(|rng| rng.borrow_mut().gen())
+}
+
+/// Makes subsequent `random` calls on the current thread deterministic.
+pub fn reseed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
 pub fn random_from(min: f32, max: f32) -> f32 {
     random::<f32>() * (max - min) + min
 }
 
+/// Uniformly distributed, so `normal + random_on_unit_sphere()` is cosine-distributed around `normal`.
 pub fn random_on_unit_sphere() -> Vec3 {
-    Vec3::new(
-        random_from(-1., 1.),
-        random_from(-1., 1.),
-        random_from(-1., 1.),
-    ).normalize()
+    let z = random_from(-1., 1.);
+    let phi = random_from(0., 2. * PI);
+    let r = (1. - z * z).max(0.).sqrt();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
 pub fn random_in_unit_disk() -> (f32, f32) {
@@ -49,5 +75,5 @@ pub fn test_image(width: usize, height: usize) -> Image {
         }
     }
 
-    return image;
+    image
 }
@@ -11,6 +11,11 @@ pub fn zero() -> Vec3 {
     Vec3::default()
 }
 
+/// Component-wise square root.
+pub fn sqrt(v: Vec3) -> Vec3 {
+    Vec3::new(v.x.sqrt(), v.y.sqrt(), v.z.sqrt())
+}
+
 pub fn refract(input_dir: Vec3, n: Vec3, angle_ratio: f32) -> Option<Vec3> {
     let input_cos = Vec3::dot(-input_dir, n);
     let input_forward = input_dir + n * input_cos;
@@ -25,6 +30,17 @@ pub fn refract(input_dir: Vec3, n: Vec3, angle_ratio: f32) -> Option<Vec3> {
     }
 }
 
+/// Two unit vectors completing `n` to a right-handed orthonormal basis (Duff et al. 2017).
+pub fn orthonormal_basis(n: Vec3) -> (Vec3, Vec3) {
+    let sign = 1f32.copysign(n.z);
+    let a = -1. / (sign + n.z);
+    let b = n.x * n.y * a;
+    (
+        Vec3::new(1. + sign * n.x * n.x * a, sign * b, -sign * n.x),
+        Vec3::new(b, sign + n.y * n.y * a, -n.y),
+    )
+}
+
 pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
     v - 2. * Vec3::dot(v, n) * n
 }
@@ -0,0 +1,261 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use glam::Vec3;
+
+use crate::my_mod::image::{HdrImage, Image};
+use crate::my_mod::rgb::RGB;
+use crate::my_mod::tonemap::DisplayTransform;
+
+/// Encodes an image into some file format, display formats take `RGB` pixels, HDR formats radiance.
+pub trait ImageWriter<P = RGB> {
+    fn write(&self, image: &Image<P>, output: &mut dyn Write) -> io::Result<()>;
+
+    fn save(&self, image: &Image<P>, path: &Path) -> io::Result<()> {
+        let mut output = BufWriter::new(File::create(path)?);
+        self.write(image, &mut output)?;
+        output.flush()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// ASCII portable pixmap (P3).
+    Ppm,
+    /// Binary portable pixmap (P6).
+    PpmBinary,
+    Png,
+    /// PNG with 16 bits per channel.
+    Png16,
+    /// OpenEXR with 32-bit float channels.
+    Exr,
+    /// Radiance RGBE.
+    Hdr,
+    /// Portable float map.
+    Pfm,
+}
+
+impl ImageFormat {
+    pub fn from_extension(path: &Path) -> Option<ImageFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "exr" => Some(ImageFormat::Exr),
+            "hdr" => Some(ImageFormat::Hdr),
+            "pfm" => Some(ImageFormat::Pfm),
+            _ => None,
+        }
+    }
+
+    pub fn supports_alpha(&self) -> bool {
+        matches!(self, ImageFormat::Png | ImageFormat::Png16 | ImageFormat::Exr)
+    }
+
+    /// HDR formats store the radiance as is, the others its conversion by `display`.
+    /// The alpha channel of the image is written if the format supports it and `alpha` is set.
+    pub fn save(&self, radiance: &HdrImage, display: &DisplayTransform, alpha: bool, path: &Path) -> io::Result<()> {
+        let hdr_writer: Box<dyn ImageWriter<Vec3>> = match self {
+            ImageFormat::Ppm => return PpmWriter { binary: false }.save(&radiance.to_display(display), path),
+            ImageFormat::PpmBinary => return PpmWriter { binary: true }.save(&radiance.to_display(display), path),
+            ImageFormat::Png => return PngWriter { sixteen_bit: false, alpha }.save(&radiance.to_display(display), path),
+            ImageFormat::Png16 => return PngWriter { sixteen_bit: true, alpha }.save(&radiance.to_display(display), path),
+            ImageFormat::Exr => Box::new(ExrWriter { alpha }),
+            ImageFormat::Hdr => Box::new(RgbeWriter),
+            ImageFormat::Pfm => Box::new(PfmWriter),
+        };
+        hdr_writer.save(radiance, path)
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ppm" => Ok(ImageFormat::Ppm),
+            "ppm-binary" => Ok(ImageFormat::PpmBinary),
+            "png" => Ok(ImageFormat::Png),
+            "png16" => Ok(ImageFormat::Png16),
+            "exr" => Ok(ImageFormat::Exr),
+            "hdr" => Ok(ImageFormat::Hdr),
+            "pfm" => Ok(ImageFormat::Pfm),
+            _ => Err(format!(
+                "unknown image format '{}', expected ppm, ppm-binary, png, png16, exr, hdr or pfm", s,
+            )),
+        }
+    }
+}
+
+fn quantize(value: f32, max: f32) -> u32 {
+    (value * max).round() as u32
+}
+
+pub struct PpmWriter {
+    pub binary: bool,
+}
+
+impl ImageWriter for PpmWriter {
+    fn write(&self, image: &Image, output: &mut dyn Write) -> io::Result<()> {
+        let magic = if self.binary { "P6" } else { "P3" };
+        write!(output, "{}\n{} {}\n255\n", magic, image.width(), image.height())?;
+
+        for i in 0..image.height() {
+            for j in 0..image.width() {
+                let rgb = image[(i, j)];
+                let r = quantize(rgb.r(), 255.);
+                let g = quantize(rgb.g(), 255.);
+                let b = quantize(rgb.b(), 255.);
+
+                if self.binary {
+                    output.write_all(&[r as u8, g as u8, b as u8])?;
+                } else {
+                    writeln!(output, "{} {} {}", r, g, b)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PngWriter {
+    pub sixteen_bit: bool,
+    pub alpha: bool,
+}
+
+impl ImageWriter for PngWriter {
+    fn write(&self, image: &Image, output: &mut dyn Write) -> io::Result<()> {
+        let alpha = self.alpha && image.has_alpha();
+
+        let mut encoder = png::Encoder::new(output, image.width() as u32, image.height() as u32);
+        encoder.set_color(if alpha { png::ColorType::Rgba } else { png::ColorType::Rgb });
+        encoder.set_depth(if self.sixteen_bit { png::BitDepth::Sixteen } else { png::BitDepth::Eight });
+        let mut writer = encoder.write_header()?;
+
+        let channels = if alpha { 4 } else { 3 };
+        let bytes_per_sample = if self.sixteen_bit { 2 } else { 1 };
+        let mut data = Vec::with_capacity(image.width() * image.height() * channels * bytes_per_sample);
+
+        for i in 0..image.height() {
+            for j in 0..image.width() {
+                let rgb = image[(i, j)];
+                let mut samples = vec![rgb.r(), rgb.g(), rgb.b()];
+                if alpha {
+                    samples.push(image.alpha(i, j));
+                }
+
+                for sample in samples {
+                    if self.sixteen_bit {
+                        data.extend_from_slice(&(quantize(sample, 65535.) as u16).to_be_bytes());
+                    } else {
+                        data.push(quantize(sample, 255.) as u8);
+                    }
+                }
+            }
+        }
+
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+pub struct ExrWriter {
+    pub alpha: bool,
+}
+
+impl ImageWriter<Vec3> for ExrWriter {
+    fn write(&self, image: &HdrImage, output: &mut dyn Write) -> io::Result<()> {
+        use exr::prelude::{Encoding, Layer, LayerAttributes, SpecificChannels, Vec2, WritableImage};
+
+        let size = (image.width(), image.height());
+        let attributes = LayerAttributes::named("rgba");
+        let pixel = |position: Vec2<usize>| image[(position.y(), position.x())];
+
+        // the encoder needs to seek, so the file is assembled in memory
+        let mut buffer = Cursor::new(vec![]);
+        let result = if self.alpha && image.has_alpha() {
+            let channels = SpecificChannels::rgba(|position: Vec2<usize>| {
+                let radiance = pixel(position);
+                (radiance.x, radiance.y, radiance.z, image.alpha(position.y(), position.x()))
+            });
+            exr::image::Image::from_layer(Layer::new(size, attributes, Encoding::SMALL_LOSSLESS, channels))
+                .write().to_buffered(&mut buffer)
+        } else {
+            let channels = SpecificChannels::rgb(|position: Vec2<usize>| {
+                let radiance = pixel(position);
+                (radiance.x, radiance.y, radiance.z)
+            });
+            exr::image::Image::from_layer(Layer::new(size, attributes, Encoding::SMALL_LOSSLESS, channels))
+                .write().to_buffered(&mut buffer)
+        };
+        result.map_err(io::Error::other)?;
+
+        output.write_all(buffer.get_ref())
+    }
+}
+
+/// Radiance `.hdr` with uncompressed scanlines of shared-exponent RGBE pixels.
+pub struct RgbeWriter;
+
+impl ImageWriter<Vec3> for RgbeWriter {
+    fn write(&self, image: &HdrImage, output: &mut dyn Write) -> io::Result<()> {
+        write!(output, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", image.height(), image.width())?;
+
+        let mut scanline = Vec::with_capacity(4 * image.width());
+        for i in 0..image.height() {
+            scanline.clear();
+            for j in 0..image.width() {
+                scanline.extend_from_slice(&to_rgbe(image[(i, j)]));
+            }
+            output.write_all(&scanline)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn to_rgbe(radiance: Vec3) -> [u8; 4] {
+    let radiance = radiance.max(Vec3::ZERO);
+    let max = radiance.max_element();
+    if max < 1e-32 || !max.is_finite() {
+        return [0; 4];
+    }
+
+    // max = mantissa * 2^exponent with the mantissa in [0.5, 1)
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max / 2f32.powi(exponent) >= 1. {
+        exponent += 1;
+    }
+    let scale = 256. / 2f32.powi(exponent);
+    [
+        (radiance.x * scale) as u8,
+        (radiance.y * scale) as u8,
+        (radiance.z * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+/// Little-endian portable float map, rows are stored bottom to top.
+pub struct PfmWriter;
+
+impl ImageWriter<Vec3> for PfmWriter {
+    fn write(&self, image: &HdrImage, output: &mut dyn Write) -> io::Result<()> {
+        write!(output, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+
+        let mut scanline = Vec::with_capacity(12 * image.width());
+        for i in (0..image.height()).rev() {
+            scanline.clear();
+            for j in 0..image.width() {
+                for value in image[(i, j)].to_array() {
+                    scanline.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            output.write_all(&scanline)?;
+        }
+
+        Ok(())
+    }
+}